@@ -1,10 +1,24 @@
-use aoc2023::day04;
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use aoc2023::day04::{self, CardTable};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 
 fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("day04::part2", |b| {
         b.iter(|| day04::part2(black_box(day04::REAL)))
     });
+
+    let table = CardTable::parse(day04::REAL).expect("valid input");
+    let mut group = c.benchmark_group("day04::scratchcards");
+    group.bench_function("linear", |b| {
+        b.iter(|| black_box(&table).process_scratchcards_linear())
+    });
+    group.bench_function("with_math", |b| {
+        b.iter_batched(
+            || table.clone(),
+            |t| t.process_scratchcards_with_math(),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
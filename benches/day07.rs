@@ -1,10 +1,47 @@
-use aoc2023::day07;
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use aoc2023::day07::{self, CardTable};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+/// Builds a table of `size` pseudo-random hands and bets.
+fn synthetic_table(size: usize) -> String {
+    const LABELS: &[u8] = b"23456789TJQKA";
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize
+    };
+
+    let mut table = String::new();
+    for _ in 0..size {
+        for _ in 0..5 {
+            table.push(LABELS[next() % LABELS.len()] as char);
+        }
+        table.push_str(&format!(" {}\n", next() % 1000));
+    }
+    table
+}
 
 fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("day07::part2", |b| {
-        b.iter(|| day07::part2(black_box(&day07::REAL)))
+        b.iter(|| day07::part2(black_box(day07::REAL)))
     });
+
+    let mut group = c.benchmark_group("day07::winnings");
+    for size in [1_000, 100_000] {
+        let table = CardTable::parse_with_jokers(&synthetic_table(size));
+        group.bench_with_input(BenchmarkId::new("key", size), &table, |b, table| {
+            b.iter(|| table.winnings())
+        });
+        group.bench_with_input(BenchmarkId::new("comparison", size), &table, |b, table| {
+            b.iter_batched(
+                || table.clone(),
+                |mut t| t.winnings_by_comparison(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
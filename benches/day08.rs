@@ -0,0 +1,27 @@
+use aoc2023::day08::{self, Map};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    c.bench_function("day08::part1", |b| {
+        b.iter(|| day08::part1(black_box(day08::REAL)))
+    });
+    c.bench_function("day08::part2", |b| {
+        b.iter(|| day08::part2(black_box(day08::REAL)))
+    });
+
+    let map = Map::parse(day08::REAL);
+    let mut group = c.benchmark_group("day08::navigate");
+    group.bench_function("by_id", |b| b.iter(|| black_box(&map).navigate_by_id()));
+    group.bench_function("compiled", |b| b.iter(|| black_box(&map).navigate()));
+    group.finish();
+
+    let mut group = c.benchmark_group("day08::navigate_ghosts");
+    group.bench_function("by_id", |b| {
+        b.iter(|| black_box(&map).navigate_ghosts_by_id())
+    });
+    group.bench_function("compiled", |b| b.iter(|| black_box(&map).navigate_ghosts()));
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
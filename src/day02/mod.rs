@@ -1,20 +1,18 @@
+use std::collections::BTreeMap;
+
 pub fn part1() {
     let input = include_str!("../../inputs/real/day02.txt");
 
-    let target_round = Round {
-        red: 12,
-        green: 13,
-        blue: 14,
-    };
+    let target_round = Round::from_pairs([("red", 12), ("green", 13), ("blue", 14)]);
 
-    let mut total: u32 = 0;
+    let mut total: u64 = 0;
     for line in input.lines() {
         let game = Game::parse(line).expect("invalid game");
         let max = game.max_possible_pulls();
         let possible = game.is_game_possible(&target_round);
 
         if possible {
-            total += game.game_id.0 as u32;
+            total += game.game_id.0 as u64;
             println!("new total: {total}");
         }
 
@@ -28,11 +26,11 @@ pub fn part1() {
 pub fn part2() {
     let input = include_str!("../../inputs/real/day02.txt");
 
-    let mut total: u32 = 0;
+    let mut total: u64 = 0;
     for line in input.lines() {
         let game = Game::parse(line).expect("invalid game");
         let max = game.max_possible_pulls();
-        let power = game.power();
+        let power = game.power().expect("power overflowed");
 
         println!("game: {game:?}");
         println!("max: {max:?}");
@@ -43,32 +41,33 @@ pub fn part2() {
     println!("total: {total}");
 }
 
-#[derive(Debug)]
-struct Game {
+#[derive(Debug, PartialEq, Eq)]
+pub struct Game {
     game_id: GameId,
     rounds: Vec<Round>,
 }
 
 impl Game {
-    fn parse(s: &str) -> Result<Self, &str> {
-        let parts = s.split_once(':');
-        if let None = parts {
-            return Err(s);
-        }
-        let (game, roundlist) = parts.expect("None case handled above");
+    pub fn parse(s: &str) -> Result<Self, &str> {
+        let (game, roundlist) = s.split_once(':').ok_or(s)?;
 
         let game_id = GameId::parse(game)?;
         let rounds = roundlist
             .split(';')
-            .map(|s| Round::parse(s))
+            .map(Round::parse)
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Game { game_id, rounds })
     }
 
-    fn power(&self) -> u32 {
+    /// Product of the minimum cube count for every color seen in the game.
+    ///
+    /// Returns `None` if the product overflows a `u64`.
+    fn power(&self) -> Option<u64> {
         let max = self.max_possible_pulls();
-        max.red as u32 * max.green as u32 * max.blue as u32
+        max.0
+            .values()
+            .try_fold(1u64, |acc, n| acc.checked_mul(*n as u64))
     }
 
     fn max_possible_pulls(&self) -> Round {
@@ -81,10 +80,38 @@ impl Game {
         let max = self.max_possible_pulls();
         target_round.other_round_fits(&max)
     }
+
+    /// Probability of seeing every round in this game if each handful was
+    /// drawn from `bag` without replacement (and put back before the next
+    /// round).
+    pub fn likelihood(&self, bag: &Round) -> f64 {
+        self.log_likelihood(bag).exp()
+    }
+
+    /// Natural log of [`Game::likelihood`]. Multiplying many small
+    /// probabilities underflows quickly, so comparisons between bags should
+    /// be done in log space. Impossible bags return negative infinity.
+    pub fn log_likelihood(&self, bag: &Round) -> f64 {
+        self.rounds
+            .iter()
+            .map(|r| bag.log_draw_probability(r))
+            .sum()
+    }
+
+    /// Ranks candidate bags by how likely they are to have produced this
+    /// game, most likely first. Each bag is paired with its log likelihood.
+    pub fn rank_bags<'a>(&self, bags: &'a [Round]) -> Vec<(&'a Round, f64)> {
+        let mut ranked: Vec<_> = bags
+            .iter()
+            .map(|bag| (bag, self.log_likelihood(bag)))
+            .collect();
+        ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        ranked
+    }
 }
 
-#[derive(Debug)]
-struct GameId(u8);
+#[derive(Debug, PartialEq, Eq)]
+struct GameId(u32);
 
 impl GameId {
     fn parse(s: &str) -> Result<Self, &str> {
@@ -98,77 +125,324 @@ impl GameId {
     }
 }
 
-#[derive(Debug)]
-struct Round {
-    red: u8,
-    green: u8,
-    blue: u8,
+/// Cube color, e.g. `red`. Colors aren't limited to the three used by the
+/// puzzle, so variant games with extra colors parse the same way.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+struct Color(String);
+
+impl Color {
+    fn new(s: &str) -> Self {
+        Self(s.to_string())
+    }
 }
 
+/// Number of cubes of a single color.
+type Count = u32;
+
+/// Multiset of cubes, mapping each color to how many times it was seen.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Round(BTreeMap<Color, Count>);
+
 impl Round {
+    pub fn from_pairs<'a>(pairs: impl IntoIterator<Item = (&'a str, Count)>) -> Round {
+        let mut round = Round::empty();
+        for (color, n) in pairs {
+            round.add(Color::new(color), n).expect("count overflowed");
+        }
+        round
+    }
+
+    fn count(&self, color: &Color) -> Count {
+        self.0.get(color).copied().unwrap_or(0)
+    }
+
+    /// Adds `n` cubes of `color` to the round, returning `None` if the
+    /// total for that color would overflow.
+    fn add(&mut self, color: Color, n: Count) -> Option<()> {
+        let entry = self.0.entry(color).or_default();
+        *entry = entry.checked_add(n)?;
+        Some(())
+    }
+
     fn pick_max(&self, other: &Round) -> Round {
-        let red = self.red.max(other.red);
-        let green = self.green.max(other.green);
-        let blue = self.blue.max(other.blue);
-        Round { red, green, blue }
+        let mut result = self.clone();
+        for (color, n) in &other.0 {
+            let entry = result.0.entry(color.clone()).or_default();
+            *entry = (*entry).max(*n);
+        }
+        result
     }
 
+    /// Returns true if every cube in `other` could have come out of this
+    /// round. Colors missing from this round count as zero cubes.
     fn other_round_fits(&self, other: &Round) -> bool {
-        self.red >= other.red && self.green >= other.green && self.blue >= other.blue
+        other.0.iter().all(|(color, n)| self.count(color) >= *n)
     }
 
-    fn empty() -> Round {
-        Round {
-            red: 0,
-            green: 0,
-            blue: 0,
+    fn total(&self) -> u64 {
+        self.0.values().map(|n| *n as u64).sum()
+    }
+
+    /// Log probability of drawing exactly `handful` from this round treated
+    /// as a bag, without replacement. This is the multivariate
+    /// hypergeometric distribution:
+    ///
+    /// ```txt
+    /// C(bag_1, drawn_1) * ... * C(bag_n, drawn_n) / C(bag_total, drawn_total)
+    /// ```
+    fn log_draw_probability(&self, handful: &Round) -> f64 {
+        if !self.other_round_fits(handful) {
+            return f64::NEG_INFINITY;
         }
+
+        let ways: f64 = handful
+            .0
+            .iter()
+            .map(|(color, n)| ln_choose(self.count(color) as u64, *n as u64))
+            .sum();
+
+        ways - ln_choose(self.total(), handful.total())
+    }
+
+    fn empty() -> Round {
+        Round(BTreeMap::new())
     }
 
     fn parse(s: &str) -> Result<Self, &str> {
         let pulls = s
             .split(',')
-            .map(|s| Pull::parse(s))
+            .map(Pull::parse)
             .collect::<Result<Vec<_>, _>>()?;
 
-        let mut green = 0;
-        let mut blue = 0;
-        let mut red = 0;
-
-        for pull in pulls {
-            match pull {
-                Pull::Red(n) => red += n,
-                Pull::Green(n) => green += n,
-                Pull::Blue(n) => blue += n,
-            }
+        let mut round = Round::empty();
+        for Pull { color, count } in pulls {
+            round.add(color, count).ok_or(s)?;
         }
 
-        Ok(Round { red, green, blue })
+        Ok(round)
     }
 }
 
 #[derive(Debug)]
-enum Pull {
-    Red(u8),
-    Green(u8),
-    Blue(u8),
+struct Pull {
+    color: Color,
+    count: Count,
 }
 
 impl Pull {
     fn parse(s: &str) -> Result<Self, &str> {
-        let parts = s.trim().split_once(' ');
-        if let None = parts {
+        let (n, color) = s.trim().split_once(' ').ok_or(s)?;
+
+        let count = n.parse().map_err(|_| s)?;
+        let color = color.trim();
+        if color.is_empty() {
             return Err(s);
         }
-        let (n, color) = parts.unwrap();
-
-        let n = n.parse().map_err(|_| s)?;
-        let pull = match color {
-            "red" => Pull::Red(n),
-            "green" => Pull::Green(n),
-            "blue" => Pull::Blue(n),
-            _ => return Err(s),
-        };
-        Ok(pull)
+
+        Ok(Pull {
+            color: Color::new(color),
+            count,
+        })
+    }
+}
+
+/// Natural log of the binomial coefficient `C(n, k)`, computed as a sum so
+/// it stays finite for counts well past where `C(n, k)` overflows.
+fn ln_choose(n: u64, k: u64) -> f64 {
+    debug_assert!(k <= n, "k must be <= n");
+    let k = k.min(n - k);
+    (0..k)
+        .map(|i| ((n - i) as f64).ln() - ((i + 1) as f64).ln())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const EXAMPLE: &str = include_str!("../../inputs/examples/day02.txt");
+
+    #[test]
+    fn game_power_example() {
+        let total: u64 = EXAMPLE
+            .lines()
+            .map(|line| Game::parse(line).expect("valid game").power().unwrap())
+            .sum();
+        assert_eq!(total, 2286);
+    }
+
+    #[test]
+    fn game_is_possible_example() {
+        let target_round = Round::from_pairs([("red", 12), ("green", 13), ("blue", 14)]);
+        let total: u32 = EXAMPLE
+            .lines()
+            .map(|line| Game::parse(line).expect("valid game"))
+            .filter(|game| game.is_game_possible(&target_round))
+            .map(|game| game.game_id.0)
+            .sum();
+        assert_eq!(total, 8);
+    }
+
+    #[test]
+    fn game_with_extra_colors() {
+        let game = Game::parse("Game 7: 2 red, 3 purple; 1 purple, 4 yellow").expect("valid game");
+        let max = game.max_possible_pulls();
+        assert_eq!(
+            max,
+            Round::from_pairs([("red", 2), ("purple", 3), ("yellow", 4)])
+        );
+        assert_eq!(game.power(), Some(2 * 3 * 4));
+
+        let bag = Round::from_pairs([("red", 12), ("green", 13), ("blue", 14)]);
+        assert!(!game.is_game_possible(&bag));
+
+        let bag = Round::from_pairs([("red", 2), ("purple", 3), ("yellow", 4)]);
+        assert!(game.is_game_possible(&bag));
+    }
+
+    #[test]
+    fn round_parse_sums_repeated_colors() {
+        let round = Round::parse(" 3 red, 4 red, 1 blue").expect("valid round");
+        assert_eq!(round, Round::from_pairs([("red", 7), ("blue", 1)]));
+    }
+
+    #[test]
+    fn pull_parse_rejects_missing_color() {
+        assert!(Pull::parse(" 3 ").is_err());
+        assert!(Pull::parse("three red").is_err());
+    }
+
+    #[test]
+    fn round_parse_wide_counts() {
+        let round = Round::parse("200 red, 100 red").expect("valid round");
+        assert_eq!(round, Round::from_pairs([("red", 300)]));
+    }
+
+    #[test]
+    fn round_parse_rejects_overflow() {
+        assert!(Round::parse("4294967295 red, 1 red").is_err());
+        assert!(Round::parse("4294967296 red").is_err());
+    }
+
+    #[test]
+    fn game_id_parse_wide() {
+        let game = Game::parse("Game 1000: 1 red").expect("valid game");
+        assert_eq!(game.game_id, GameId(1000));
+        assert!(GameId::parse("Game 4294967296").is_err());
+    }
+
+    #[test]
+    fn game_power_overflow() {
+        let game = Game::parse("Game 1: 4294967295 red, 4294967295 green, 4294967295 blue")
+            .expect("valid game");
+        assert_eq!(game.power(), None);
+    }
+
+    prop_compose! {
+        fn gen_pull()(color in "[a-z]{1,8}", count in 0..10_000u32) -> (String, u32) {
+            (color, count)
+        }
+    }
+
+    prop_compose! {
+        fn gen_game()(
+            id: u32,
+            rounds in prop::collection::vec(prop::collection::vec(gen_pull(), 1..6), 1..6),
+        ) -> (u32, Vec<Vec<(String, u32)>>) {
+            (id, rounds)
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn game_parse_round_trip((id, rounds) in gen_game()) {
+            let line = format!(
+                "Game {id}: {}",
+                rounds
+                    .iter()
+                    .map(|pulls| pulls
+                        .iter()
+                        .map(|(color, n)| format!("{n} {color}"))
+                        .collect::<Vec<_>>()
+                        .join(", "))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            );
+            let game = Game::parse(&line).expect("valid game");
+
+            assert_eq!(game.game_id, GameId(id));
+            assert_eq!(game.rounds.len(), rounds.len());
+
+            // oracle: sum repeated colors per round, take the max per color
+            // across rounds, then multiply everything in u64 space.
+            let mut max: BTreeMap<&str, u64> = BTreeMap::new();
+            for (pulls, round) in rounds.iter().zip(&game.rounds) {
+                let mut sums: BTreeMap<&str, u64> = BTreeMap::new();
+                for (color, n) in pulls {
+                    *sums.entry(color).or_default() += *n as u64;
+                }
+                for (color, n) in &sums {
+                    assert_eq!(round.count(&Color::new(color)) as u64, *n);
+                    let m = max.entry(color).or_default();
+                    *m = (*m).max(*n);
+                }
+            }
+            let oracle = max.values().try_fold(1u64, |acc, n| acc.checked_mul(*n));
+
+            assert_eq!(game.power(), oracle);
+        }
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() <= 1e-9 * b.abs().max(1.0), "{a} != {b}");
+    }
+
+    #[test]
+    fn ln_choose_small() {
+        assert_close(ln_choose(4, 2).exp(), 6.0);
+        assert_close(ln_choose(10, 0).exp(), 1.0);
+        assert_close(ln_choose(10, 10).exp(), 1.0);
+        assert_close(ln_choose(52, 5).exp(), 2_598_960.0);
+    }
+
+    #[test]
+    fn game_likelihood_single_round() {
+        let game = Game::parse("Game 1: 1 red").expect("valid game");
+        let bag = Round::from_pairs([("red", 1), ("blue", 1)]);
+        assert_close(game.likelihood(&bag), 0.5);
+
+        let game = Game::parse("Game 1: 1 red, 1 blue").expect("valid game");
+        let bag = Round::from_pairs([("red", 2), ("blue", 2)]);
+        assert_close(game.likelihood(&bag), 4.0 / 6.0);
+    }
+
+    #[test]
+    fn game_likelihood_multiplies_rounds() {
+        let game = Game::parse("Game 1: 1 red; 1 blue").expect("valid game");
+        let bag = Round::from_pairs([("red", 1), ("blue", 3)]);
+        assert_close(game.likelihood(&bag), 0.25 * 0.75);
+    }
+
+    #[test]
+    fn game_likelihood_impossible_bag() {
+        let game = Game::parse("Game 1: 2 red; 1 green").expect("valid game");
+        let bag = Round::from_pairs([("red", 5), ("blue", 5)]);
+        assert_eq!(game.likelihood(&bag), 0.0);
+        assert_eq!(game.log_likelihood(&bag), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn game_rank_bags() {
+        let game = Game::parse("Game 1: 1 red; 1 red; 2 red; 1 blue").expect("valid game");
+        let bags = [
+            Round::from_pairs([("red", 1), ("blue", 9)]),
+            Round::from_pairs([("red", 9), ("blue", 1)]),
+            Round::from_pairs([("red", 5), ("blue", 5)]),
+            Round::from_pairs([("blue", 10)]),
+        ];
+
+        let ranked: Vec<_> = game.rank_bags(&bags).into_iter().map(|(b, _)| b).collect();
+        assert_eq!(ranked, vec![&bags[1], &bags[2], &bags[0], &bags[3]]);
     }
 }
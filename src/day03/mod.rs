@@ -1,11 +1,17 @@
-use crate::util::{Parser, Range};
-use std::collections::{BTreeMap, HashSet};
+use crate::util::{self, Grid, Range};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use std::iter;
 
 pub const EXAMPLE: &str = include_str!("../../inputs/examples/day03.txt");
 pub const REAL: &str = include_str!("../../inputs/real/day03.txt");
 
 pub fn part1(input: &str) -> u32 {
-    let mut parser = SchematicParser::new(input);
+    part1_with_config(input, &SchematicConfig::default())
+}
+
+pub fn part1_with_config(input: &str, config: &SchematicConfig) -> u32 {
+    let mut parser = SchematicParser::with_config(input, config.clone());
 
     // since we only need to for adjacency between gears and parts, not all
     // tokens, we can skip some work later by tracking them separately up front.
@@ -18,7 +24,8 @@ pub fn part1(input: &str) -> u32 {
     while let Some(token) = parser.parse_token() {
         let map = match token {
             Token::Part { .. } => &mut part_map,
-            Token::Symbol { .. } => &mut symbol_map,
+            t if config.is_activator(&t) => &mut symbol_map,
+            _ => continue,
         };
         map.insert(token);
     }
@@ -33,12 +40,7 @@ pub fn part1(input: &str) -> u32 {
     let mut adjacent_parts: HashSet<Token> = HashSet::new();
     for (line, symbols) in symbol_map {
         let is_adjacent = |t: &&Token| symbols.iter().any(|s| s.is_adjacent(t));
-        let surrounding_lines = line - 1..=line + 1;
-
-        let adjacent = surrounding_lines
-            .filter_map(|l| part_map.get(l))
-            .flatten()
-            .filter(is_adjacent);
+        let adjacent = part_map.nearby_tokens(line).into_iter().filter(is_adjacent);
 
         adjacent_parts.extend(adjacent);
     }
@@ -49,8 +51,12 @@ pub fn part1(input: &str) -> u32 {
         .sum()
 }
 
-pub fn part2(s: &str) -> u32 {
-    let mut parser = SchematicParser::new(s);
+pub fn part2(s: &str) -> u64 {
+    part2_with_config(s, &SchematicConfig::default())
+}
+
+pub fn part2_with_config(s: &str, config: &SchematicConfig) -> u64 {
+    let mut parser = SchematicParser::with_config(s, config.clone());
 
     // similar to part 1, but we can save even more work by only tracking
     // the gears instead of all the symbols. we still need to track all parts.
@@ -59,18 +65,19 @@ pub fn part2(s: &str) -> u32 {
     while let Some(token) = parser.parse_token() {
         let map = match token {
             t if t.is_part() => &mut part_map,
-            t if t.is_possible_gear() => &mut gear_map,
+            t if config.is_gear(&t) => &mut gear_map,
             _ => continue,
         };
         map.insert(token);
     }
 
-    let mut total_ratio = 0;
+    let mut total_ratio: u64 = 0;
     for (line, gears) in gear_map {
         let nearby = part_map.nearby_tokens(line);
 
-        // spec requires us to have exactly two parts attached to a gear,
-        // so we bail early if we find anything different.
+        // spec requires us to have exactly `gear_parts` parts attached to
+        // a gear (two by default), so we bail early if we find anything
+        // different.
         for gear in gears {
             let adjacent = nearby
                 .iter()
@@ -79,19 +86,117 @@ pub fn part2(s: &str) -> u32 {
                 .collect::<Result<Vec<_>, String>>()
                 .expect("should only be parts in the part_map");
 
-            if adjacent.len() != 2 {
+            if adjacent.len() != config.gear_parts {
                 continue;
             }
 
-            total_ratio += adjacent[0] * adjacent[1];
+            // a ratio of a few big part numbers soon outgrows even a u64,
+            // so it's better to stop than to wrap around
+            let ratio = adjacent
+                .iter()
+                .try_fold(1u64, |ratio, part| ratio.checked_mul(u64::from(*part)))
+                .expect("gear ratio overflowed");
+            total_ratio = total_ratio
+                .checked_add(ratio)
+                .expect("total gear ratio overflowed");
         }
     }
 
     total_ratio
 }
 
+/// Describes how to read a schematic: which characters are blank, which
+/// symbols activate adjacent parts, and what makes a gear.
+///
+/// The default matches the puzzle: `.` is blank, every symbol activates its
+/// neighbours, and a gear is a `*` with exactly two adjacent parts.
+/// Whitespace is always blank.
+///
+/// # Examples
+/// ```
+/// use aoc2023::day03::{self, SchematicConfig};
+///
+/// let config = SchematicConfig::default()
+///     .with_blanks("_")
+///     .with_activators("@")
+///     .with_gear('@')
+///     .with_gear_parts(3);
+///
+/// let schematic = "1_2\n_@_\n3#4";
+/// assert_eq!(day03::part1_with_config(schematic, &config), 1 + 2 + 3 + 4);
+/// assert_eq!(day03::part2_with_config(schematic, &config), 0);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SchematicConfig {
+    blanks: String,
+    activators: Option<String>,
+    gear: char,
+    gear_parts: usize,
+}
+
+impl Default for SchematicConfig {
+    fn default() -> Self {
+        Self {
+            blanks: String::from("."),
+            activators: None,
+            gear: '*',
+            gear_parts: 2,
+        }
+    }
+}
+
+impl SchematicConfig {
+    /// Sets the characters treated as empty space.
+    pub fn with_blanks(mut self, blanks: &str) -> Self {
+        self.blanks = blanks.to_string();
+        self
+    }
+
+    /// Restricts the symbols that make adjacent parts count. Any other
+    /// symbol is still parsed, but doesn't activate anything.
+    pub fn with_activators(mut self, activators: &str) -> Self {
+        self.activators = Some(activators.to_string());
+        self
+    }
+
+    /// Sets the symbol that marks a possible gear.
+    pub fn with_gear(mut self, gear: char) -> Self {
+        self.gear = gear;
+        self
+    }
+
+    /// Sets exactly how many parts must be adjacent to a gear for it to
+    /// count. The gear ratio is the product of all of them.
+    ///
+    /// Panics if `n` is zero, since a gear with no parts has no ratio.
+    pub fn with_gear_parts(mut self, n: usize) -> Self {
+        assert!(n > 0, "a gear needs at least one part");
+        self.gear_parts = n;
+        self
+    }
+
+    fn is_blank(&self, c: char) -> bool {
+        c.is_whitespace() || self.blanks.contains(c)
+    }
+
+    fn is_activator(&self, token: &Token) -> bool {
+        match (token, &self.activators) {
+            (Token::Symbol { .. }, None) => true,
+            (Token::Symbol { symbol, .. }, Some(activators)) => activators.contains(*symbol),
+            _ => false,
+        }
+    }
+
+    fn is_gear(&self, token: &Token) -> bool {
+        match token {
+            Token::Symbol { symbol, .. } => *symbol == self.gear,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-enum Token {
+pub enum Token {
     /// Represents a part number in the engine schematic, e.g. `312`
     Part {
         number: u32,
@@ -131,24 +236,11 @@ impl Token {
 
     #[allow(unused)]
     fn is_symbol(&self) -> bool {
-        match self {
-            Token::Symbol { .. } => true,
-            _ => false,
-        }
-    }
-
-    fn is_possible_gear(&self) -> bool {
-        match self {
-            Token::Symbol { symbol, .. } => *symbol == '*',
-            _ => false,
-        }
+        matches!(self, Token::Symbol { .. })
     }
 
     fn is_part(&self) -> bool {
-        match self {
-            Token::Part { .. } => true,
-            _ => false,
-        }
+        matches!(self, Token::Part { .. })
     }
 
     /// Returns true if the `other` token is adjacent to this token.
@@ -168,8 +260,9 @@ impl Token {
     }
 
     fn is_adjacent_y_axis(&self, other: &Self) -> bool {
+        // saturate so tokens on line 0 (or u32::MAX) don't wrap around
         let this_line = self.line();
-        (this_line - 1..=this_line + 1).contains(&other.line())
+        (this_line.saturating_sub(1)..=this_line.saturating_add(1)).contains(&other.line())
     }
 
     fn is_adjacent_x_axis(&self, other: &Self) -> bool {
@@ -177,48 +270,85 @@ impl Token {
     }
 }
 
+/// Walks a schematic [`Grid`] left to right, top to bottom, producing tokens.
+///
+/// Token lines and columns are 1-indexed, so the token for the cell at grid
+/// position `(0, 0)` is on line 1 with range `1..2`.
 #[derive(Debug)]
-struct SchematicParser<'a>(Parser<'a>);
+struct SchematicParser {
+    grid: Grid<char>,
+    config: SchematicConfig,
+    row: usize,
+    col: usize,
+}
 
-impl<'a> SchematicParser<'a> {
-    fn new(input: &'a str) -> Self {
-        Self(Parser::new(input))
+impl SchematicParser {
+    #[cfg(test)]
+    fn new(input: &str) -> Self {
+        Self::with_config(input, SchematicConfig::default())
     }
 
-    fn parse_token(&mut self) -> Option<Token> {
-        let p = &mut self.0;
+    fn with_config(input: &str, config: SchematicConfig) -> Self {
+        Self {
+            grid: schematic_grid(input),
+            config,
+            row: 0,
+            col: 0,
+        }
+    }
 
+    fn parse_token(&mut self) -> Option<Token> {
         // drop whitespace. whatever comes after will be a token or EOF
-        p.next_while(|c| c == '.' || c.is_whitespace());
-
-        // we won't cross a line boundary during token parsing
-        let line = p.line();
+        let (row, cells) = loop {
+            let cells = self.grid.row(self.row)?;
+            match cells.get(self.col) {
+                None => {
+                    self.row += 1;
+                    self.col = 0;
+                }
+                Some(c) if self.config.is_blank(*c) => self.col += 1,
+                Some(_) => break (self.row, cells),
+            }
+        };
 
-        // keep track of starting position for building the token range
-        let start_column = p.column();
+        // tokens never cross a row boundary, so everything we need is in
+        // this row's slice
+        let line = row as u32 + 1;
+        let start = self.col;
 
         // try to parse a number
-        if let Some(number) = p.next_while(|c| c.is_digit(10)) {
-            let range = Range::new(start_column, p.column());
-            let number = number
+        let digits = cells[start..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+
+        if digits > 0 {
+            self.col += digits;
+            let number = cells[start..self.col]
+                .iter()
+                .collect::<String>()
                 .parse()
                 .expect("filtered for digits, should have a number");
             return Some(Token::Part {
                 number,
                 line,
-                range,
+                range: Self::range(start, self.col),
             });
         }
 
         // everything else is a symbol
-        let symbol = p.next()?;
-        let range = Range::new(start_column, p.column());
+        let symbol = cells[start];
+        self.col += 1;
         Some(Token::Symbol {
             symbol,
             line,
-            range,
+            range: Self::range(start, self.col),
         })
     }
+
+    fn range(start: usize, end: usize) -> Range {
+        Range::new(start as u32 + 1, end as u32 + 1)
+    }
 }
 
 /// LineTokenMap is a map of line numbers to tokens on that line
@@ -238,9 +368,11 @@ impl LineTokenMap {
         line_tokens.push(token);
     }
 
-    /// Returns tokens from the line above, the line below, and the current line
+    /// Returns tokens from the line above, the line below, and the current line.
+    ///
+    /// Lines outside `0..=usize::MAX` are skipped rather than wrapped.
     fn nearby_tokens(&self, line: usize) -> Vec<&Token> {
-        let nearby_lines = line - 1..=line + 1;
+        let nearby_lines = line.saturating_sub(1)..=line.saturating_add(1);
         nearby_lines.filter_map(|l| self.get(l)).flatten().collect()
     }
 
@@ -261,9 +393,348 @@ impl IntoIterator for LineTokenMap {
     }
 }
 
+/// Lays a schematic out on a grid. Lines shorter than the longest one,
+/// including blank lines, are padded with spaces, which are skipped like
+/// any other whitespace, so ragged input parses the same as it would line
+/// by line.
+fn schematic_grid(input: &str) -> Grid<char> {
+    let width = input.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+    let cells = input
+        .lines()
+        .flat_map(|l| l.chars().chain(iter::repeat(' ')).take(width))
+        .collect();
+    Grid::new(width, input.lines().count(), cells)
+}
+
+/// Full bipartite adjacency between the symbols and parts of a schematic.
+///
+/// Both sides are kept in schematic order (top to bottom, left to right), and
+/// every token is listed even if it has no neighbours, so parts that were
+/// never counted show up with an empty symbol list.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SchematicGraph {
+    config: SchematicConfig,
+    symbols: Vec<(Token, Vec<Token>)>,
+    parts: Vec<(Token, Vec<Token>)>,
+}
+
+impl SchematicGraph {
+    pub fn parse(input: &str) -> Self {
+        Self::parse_with_config(input, &SchematicConfig::default())
+    }
+
+    pub fn parse_with_config(input: &str, config: &SchematicConfig) -> Self {
+        let mut parser = SchematicParser::with_config(input, config.clone());
+
+        let mut part_map = LineTokenMap::new();
+        let mut symbols = vec![];
+        let mut parts = vec![];
+        while let Some(token) = parser.parse_token() {
+            match token {
+                Token::Part { .. } => {
+                    parts.push(token);
+                    part_map.insert(token);
+                }
+                Token::Symbol { .. } => symbols.push(token),
+            }
+        }
+
+        let mut part_symbols: HashMap<Token, Vec<Token>> = HashMap::new();
+        let symbols: Vec<_> = symbols
+            .into_iter()
+            .map(|symbol| {
+                let adjacent: Vec<Token> = part_map
+                    .nearby_tokens(symbol.line() as usize)
+                    .into_iter()
+                    .filter(|p| symbol.is_adjacent(p))
+                    .copied()
+                    .collect();
+
+                for part in &adjacent {
+                    part_symbols.entry(*part).or_default().push(symbol);
+                }
+                (symbol, adjacent)
+            })
+            .collect();
+
+        let parts = parts
+            .into_iter()
+            .map(|part| {
+                let adjacent = part_symbols.remove(&part).unwrap_or_default();
+                (part, adjacent)
+            })
+            .collect();
+
+        Self {
+            config: config.clone(),
+            symbols,
+            parts,
+        }
+    }
+
+    /// Returns every symbol along with the parts adjacent to it.
+    pub fn symbols(&self) -> impl Iterator<Item = (&Token, &[Token])> {
+        self.symbols.iter().map(|(t, adj)| (t, adj.as_slice()))
+    }
+
+    /// Returns every part along with the symbols adjacent to it.
+    pub fn parts(&self) -> impl Iterator<Item = (&Token, &[Token])> {
+        self.parts.iter().map(|(t, adj)| (t, adj.as_slice()))
+    }
+
+    /// Returns every possible gear (`*` by default) along with the parts
+    /// adjacent to it, regardless of how many parts that is.
+    pub fn gears(&self) -> impl Iterator<Item = (&Token, &[Token])> {
+        self.symbols().filter(|(t, _)| self.config.is_gear(t))
+    }
+
+    /// Renders the graph in Graphviz DOT format. Possible gears are filled,
+    /// and parts with no adjacent activating symbols are drawn dashed.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("graph schematic {\n");
+
+        for (symbol, _) in &self.symbols {
+            let style = if self.config.is_gear(symbol) {
+                ", style=filled, fillcolor=gold"
+            } else {
+                ""
+            };
+            let label = util::dot_escape(&symbol.label());
+            let id = symbol.node_id();
+            writeln!(out, "  {id} [label=\"{label}\", shape=box{style}];").unwrap();
+        }
+
+        for (part, symbols) in &self.parts {
+            let style = if !symbols.iter().any(|s| self.config.is_activator(s)) {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            let label = part.label();
+            let id = part.node_id();
+            writeln!(out, "  {id} [label=\"{label}\", shape=ellipse{style}];").unwrap();
+        }
+
+        for (symbol, parts) in &self.symbols {
+            for part in parts {
+                writeln!(out, "  {} -- {};", symbol.node_id(), part.node_id()).unwrap();
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders the graph as JSON, with a `symbols` list (each with its
+    /// adjacent `parts`) and a `parts` list (each with its adjacent
+    /// `symbols`).
+    pub fn to_json(&self) -> String {
+        let side = |entries: &[(Token, Vec<Token>)], key: &str| {
+            let items: Vec<String> = entries
+                .iter()
+                .map(|(token, adjacent)| {
+                    let adjacent: Vec<String> = adjacent.iter().map(Token::json).collect();
+                    let fields = token.json_fields();
+                    format!("{{{fields},\"{key}\":[{}]}}", adjacent.join(","))
+                })
+                .collect();
+            format!("[{}]", items.join(","))
+        };
+
+        format!(
+            "{{\"symbols\":{},\"parts\":{}}}",
+            side(&self.symbols, "parts"),
+            side(&self.parts, "symbols"),
+        )
+    }
+}
+
+impl Token {
+    /// Stable, unique identifier for graph exports, e.g. `p1_1` for a part
+    /// starting on line 1, column 1.
+    fn node_id(&self) -> String {
+        let kind = if self.is_part() { 'p' } else { 's' };
+        format!("{kind}{}_{}", self.line(), self.range().start())
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Token::Part { number, .. } => number.to_string(),
+            Token::Symbol { symbol, .. } => symbol.to_string(),
+        }
+    }
+
+    fn json_fields(&self) -> String {
+        let (line, range) = (self.line(), self.range());
+        let position = format!(
+            "\"line\":{line},\"start\":{},\"end\":{}",
+            range.start(),
+            range.end()
+        );
+        match self {
+            Token::Part { number, .. } => format!("\"number\":{number},{position}"),
+            Token::Symbol { symbol, .. } => {
+                let symbol = util::json_escape(&symbol.to_string());
+                format!("\"symbol\":\"{symbol}\",{position}")
+            }
+        }
+    }
+
+    fn json(&self) -> String {
+        format!("{{{}}}", self.json_fields())
+    }
+}
+
+/// How a cell is highlighted when rendering an annotated schematic.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Highlight {
+    /// A part adjacent to at least one activating symbol, counted by `part1`
+    CountedPart,
+
+    /// A part with no adjacent activating symbols
+    UncountedPart,
+
+    /// A gear with exactly the required number of parts, counted by `part2`
+    Gear,
+
+    /// A gear symbol with the wrong number of adjacent parts
+    BadGear,
+}
+
+impl Highlight {
+    fn ansi(&self) -> &'static str {
+        match self {
+            Highlight::CountedPart => "\x1b[32m",
+            Highlight::UncountedPart => "\x1b[31m",
+            Highlight::Gear => "\x1b[1;33m",
+            Highlight::BadGear => "\x1b[1;35m",
+        }
+    }
+
+    fn css_class(&self) -> &'static str {
+        match self {
+            Highlight::CountedPart => "counted-part",
+            Highlight::UncountedPart => "uncounted-part",
+            Highlight::Gear => "gear",
+            Highlight::BadGear => "bad-gear",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RenderFormat {
+    /// Terminal output using ANSI color escapes
+    Ansi,
+
+    /// A `<pre>` block with a `<span class="...">` around each highlight
+    Html,
+}
+
+/// Reprints the schematic with every part and gear highlighted according to
+/// how `part1` and `part2` treat it under the given config.
+///
+/// Other symbols and blanks are printed unchanged.
+pub fn render(input: &str, config: &SchematicConfig, format: RenderFormat) -> String {
+    let grid = schematic_grid(input);
+    let graph = SchematicGraph::parse_with_config(input, config);
+
+    let mut highlights = grid.map(|_| None);
+    let mut mark = |token: &Token, highlight: Highlight| {
+        // tokens are 1-indexed, the grid is 0-indexed
+        let row = token.line() as usize - 1;
+        let range = token.range();
+        for col in range.start()..range.end() {
+            highlights.set(row, col as usize - 1, Some(highlight));
+        }
+    };
+
+    for (part, symbols) in graph.parts() {
+        let highlight = if symbols.iter().any(|s| config.is_activator(s)) {
+            Highlight::CountedPart
+        } else {
+            Highlight::UncountedPart
+        };
+        mark(part, highlight);
+    }
+
+    for (gear, parts) in graph.gears() {
+        let highlight = if parts.len() == config.gear_parts {
+            Highlight::Gear
+        } else {
+            Highlight::BadGear
+        };
+        mark(gear, highlight);
+    }
+
+    let mut out = String::new();
+    if format == RenderFormat::Html {
+        out.push_str("<pre class=\"schematic\">");
+    }
+
+    for (row, (cells, line)) in grid.rows().zip(input.lines()).enumerate() {
+        if row > 0 {
+            out.push('\n');
+        }
+
+        // leave out the padding added to short lines
+        let cells = &cells[..line.chars().count()];
+
+        let mut current = None;
+        for (col, c) in cells.iter().enumerate() {
+            let highlight = highlights[(row, col)];
+            if highlight != current {
+                if current.is_some() {
+                    out.push_str(close(format));
+                }
+                if let Some(h) = highlight {
+                    match format {
+                        RenderFormat::Ansi => out.push_str(h.ansi()),
+                        RenderFormat::Html => {
+                            write!(out, "<span class=\"{}\">", h.css_class()).unwrap()
+                        }
+                    }
+                }
+                current = highlight;
+            }
+
+            match format {
+                RenderFormat::Ansi => out.push(*c),
+                RenderFormat::Html => out.push_str(&html_escape(*c)),
+            }
+        }
+
+        if current.is_some() {
+            out.push_str(close(format));
+        }
+    }
+
+    if format == RenderFormat::Html {
+        out.push_str("</pre>\n");
+    }
+    out
+}
+
+fn close(format: RenderFormat) -> &'static str {
+    match format {
+        RenderFormat::Ansi => "\x1b[0m",
+        RenderFormat::Html => "</span>",
+    }
+}
+
+fn html_escape(c: char) -> String {
+    match c {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        c => c.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_parse_token() {
@@ -349,4 +820,376 @@ mod test {
         println!("part2: {}", total);
         assert_eq!(total, 81166799);
     }
+
+    #[test]
+    fn ragged_schematic() {
+        assert_eq!(part1("1*.\n...\n\n"), 1);
+
+        let schematic = "12\n.*...\n3\n\n";
+        assert_eq!(part1(schematic), 15);
+        assert_eq!(part2(schematic), 36);
+
+        let rendered = render(schematic, &SchematicConfig::default(), RenderFormat::Ansi);
+        let expected = concat!(
+            "\x1b[32m12\x1b[0m\n",
+            ".\x1b[1;33m*\x1b[0m...\n",
+            "\x1b[32m3\x1b[0m\n",
+        );
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn schematic_graph_example() {
+        let graph = SchematicGraph::parse(EXAMPLE);
+        assert_eq!(graph.symbols().count(), 6);
+        assert_eq!(graph.parts().count(), 10);
+
+        let first_gear: Vec<u32> = graph
+            .gears()
+            .next()
+            .expect("example has gears")
+            .1
+            .iter()
+            .map(|p| p.try_part_number().unwrap())
+            .collect();
+        assert_eq!(first_gear, vec![467, 35]);
+
+        let unattached: Vec<u32> = graph
+            .parts()
+            .filter(|(_, symbols)| symbols.is_empty())
+            .map(|(p, _)| p.try_part_number().unwrap())
+            .collect();
+        assert_eq!(unattached, vec![114, 58]);
+
+        // the graph agrees with the part1 and part2 sums
+        let total: u32 = graph
+            .parts()
+            .filter(|(_, symbols)| !symbols.is_empty())
+            .map(|(p, _)| p.try_part_number().unwrap())
+            .sum();
+        assert_eq!(total, part1(EXAMPLE));
+
+        let ratio: u64 = graph
+            .gears()
+            .filter(|(_, parts)| parts.len() == 2)
+            .map(|(_, parts)| {
+                let part = |i: usize| u64::from(parts[i].try_part_number().unwrap());
+                part(0) * part(1)
+            })
+            .sum();
+        assert_eq!(ratio, part2(EXAMPLE));
+    }
+
+    #[test]
+    fn schematic_graph_shared_part() {
+        let graph = SchematicGraph::parse("*11#\n....");
+        let (part, symbols) = graph.parts().next().unwrap();
+        assert_eq!(part.try_part_number(), Ok(11));
+        assert_eq!(symbols.len(), 2);
+    }
+
+    #[test]
+    fn schematic_graph_to_dot() {
+        let graph = SchematicGraph::parse("1*.\n..2\n\"..");
+        let dot = graph.to_dot();
+        let expected = "graph schematic {
+  s1_2 [label=\"*\", shape=box, style=filled, fillcolor=gold];
+  s3_1 [label=\"\\\"\", shape=box];
+  p1_1 [label=\"1\", shape=ellipse];
+  p2_3 [label=\"2\", shape=ellipse];
+  s1_2 -- p1_1;
+  s1_2 -- p2_3;
+}
+";
+        assert_eq!(dot, expected);
+    }
+
+    #[test]
+    fn schematic_graph_to_json() {
+        let graph = SchematicGraph::parse("1*\n..\n.5");
+        let json = graph.to_json();
+        let expected = concat!(
+            "{\"symbols\":[",
+            "{\"symbol\":\"*\",\"line\":1,\"start\":2,\"end\":3,",
+            "\"parts\":[{\"number\":1,\"line\":1,\"start\":1,\"end\":2}]}",
+            "],\"parts\":[",
+            "{\"number\":1,\"line\":1,\"start\":1,\"end\":2,",
+            "\"symbols\":[{\"symbol\":\"*\",\"line\":1,\"start\":2,\"end\":3}]},",
+            "{\"number\":5,\"line\":3,\"start\":2,\"end\":3,\"symbols\":[]}",
+            "]}"
+        );
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn test_is_adjacent_line_zero() {
+        let part = Token::Part {
+            number: 1,
+            line: 0,
+            range: Range::new(0, 1),
+        };
+        let symbol = Token::Symbol {
+            symbol: '*',
+            line: 0,
+            range: Range::new(1, 2),
+        };
+        let below = Token::Symbol {
+            symbol: '*',
+            line: 1,
+            range: Range::new(0, 1),
+        };
+        let far = Token::Symbol {
+            symbol: '*',
+            line: 2,
+            range: Range::new(0, 1),
+        };
+
+        assert!(part.is_adjacent(&symbol));
+        assert!(part.is_adjacent(&below));
+        assert!(below.is_adjacent(&part));
+        assert!(!part.is_adjacent(&far));
+    }
+
+    #[test]
+    fn test_is_adjacent_max_line() {
+        let a = Token::Part {
+            number: 1,
+            line: u32::MAX,
+            range: Range::new(0, 1),
+        };
+        let b = Token::Symbol {
+            symbol: '#',
+            line: u32::MAX - 1,
+            range: Range::new(1, 2),
+        };
+        assert!(a.is_adjacent(&b));
+        assert!(b.is_adjacent(&a));
+    }
+
+    #[test]
+    fn test_nearby_tokens_line_zero() {
+        let mut map = LineTokenMap::new();
+        for line in 0..3 {
+            map.insert(Token::Symbol {
+                symbol: '*',
+                line,
+                range: Range::new(0, 1),
+            });
+        }
+        assert_eq!(map.nearby_tokens(0).len(), 2);
+        assert_eq!(map.nearby_tokens(1).len(), 3);
+        assert_eq!(map.nearby_tokens(usize::MAX).len(), 0);
+    }
+
+    #[test]
+    fn test_symbols_on_edges() {
+        // symbols in the first and last line and column
+        let schematic = "#12.3\n.....\n4..5$";
+        assert_eq!(part1(schematic), 12 + 5);
+
+        let schematic = "2*\n3.";
+        assert_eq!(part1(schematic), 5);
+        assert_eq!(part2(schematic), 6);
+
+        let schematic = "..7\n..*\n..8";
+        assert_eq!(part1(schematic), 15);
+        assert_eq!(part2(schematic), 56);
+    }
+
+    /// Brute-force `(part1, part2)` over the raw grid: find every run of
+    /// digits, then look at all eight neighbours of every digit in the run.
+    fn grid_oracle(input: &str) -> (u32, u64) {
+        let grid = Grid::parse(input).unwrap();
+        let is_symbol = |c: &char| !c.is_ascii_digit() && *c != '.';
+
+        let mut numbers = vec![];
+        for (row, cells) in grid.rows().enumerate() {
+            let mut col = 0;
+            while col < cells.len() {
+                let len = cells[col..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                if len == 0 {
+                    col += 1;
+                    continue;
+                }
+                let number: u32 = cells[col..col + len]
+                    .iter()
+                    .collect::<String>()
+                    .parse()
+                    .unwrap();
+                let neighbours: HashSet<(usize, usize)> = (col..col + len)
+                    .flat_map(|c| grid.neighbours8(row, c))
+                    .filter(|pos| is_symbol(&grid[*pos]))
+                    .collect();
+                numbers.push((number, neighbours));
+                col += len;
+            }
+        }
+
+        let part1 = numbers
+            .iter()
+            .filter(|(_, symbols)| !symbols.is_empty())
+            .map(|(n, _)| n)
+            .sum();
+
+        let part2 = grid
+            .find_all(|c| *c == '*')
+            .map(|gear| {
+                numbers
+                    .iter()
+                    .filter(|(_, symbols)| symbols.contains(&gear))
+                    .map(|(n, _)| *n)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|adjacent| adjacent.len() == 2)
+            .map(|adjacent| u64::from(adjacent[0]) * u64::from(adjacent[1]))
+            .sum();
+
+        (part1, part2)
+    }
+
+    prop_compose! {
+        fn gen_schematic()(width in 1..12usize, height in 1..12usize)
+            (rows in prop::collection::vec(
+                prop::collection::vec(prop::sample::select(vec!['.', '.', '.', '1', '2', '9', '0', '*', '#']), width),
+                height,
+            )) -> String {
+            rows.into_iter()
+                .map(|mut row| {
+                    // cap numbers at three digits so part2 products stay small
+                    let mut run = 0;
+                    for c in row.iter_mut() {
+                        run = if c.is_ascii_digit() { run + 1 } else { 0 };
+                        if run > 3 {
+                            *c = '.';
+                            run = 0;
+                        }
+                    }
+                    row.into_iter().collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn parts_match_grid_oracle(schematic in gen_schematic()) {
+            let (part1_oracle, part2_oracle) = grid_oracle(&schematic);
+            assert_eq!(part1(&schematic), part1_oracle);
+            assert_eq!(part2(&schematic), part2_oracle);
+        }
+    }
+
+    #[test]
+    fn config_default_matches_puzzle() {
+        let config = SchematicConfig::default();
+        assert_eq!(part1_with_config(EXAMPLE, &config), 4361);
+        assert_eq!(part2_with_config(EXAMPLE, &config), 467835);
+    }
+
+    #[test]
+    fn config_blanks() {
+        // `_` is a symbol by default, so 1 counts. once it's blank, it doesn't.
+        let schematic = "1_.\n...";
+        assert_eq!(part1(schematic), 1);
+
+        let config = SchematicConfig::default().with_blanks("._");
+        assert_eq!(part1_with_config(schematic, &config), 0);
+    }
+
+    #[test]
+    fn config_activators() {
+        let schematic = "1#.2\n...$";
+        assert_eq!(part1(schematic), 3);
+
+        let config = SchematicConfig::default().with_activators("$");
+        assert_eq!(part1_with_config(schematic, &config), 2);
+
+        let config = SchematicConfig::default().with_activators("");
+        assert_eq!(part1_with_config(schematic, &config), 0);
+    }
+
+    #[test]
+    fn config_gear_symbol_and_parts() {
+        let schematic = "2.3\n.@.\n..4";
+        assert_eq!(part2(schematic), 0);
+
+        let config = SchematicConfig::default().with_gear('@');
+        assert_eq!(part2_with_config(schematic, &config), 0);
+
+        let config = config.with_gear_parts(3);
+        assert_eq!(part2_with_config(schematic, &config), 24);
+
+        let graph = SchematicGraph::parse_with_config(schematic, &config);
+        assert_eq!(graph.gears().count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "a gear needs at least one part")]
+    fn config_no_gear_parts() {
+        SchematicConfig::default().with_gear_parts(0);
+    }
+
+    #[test]
+    fn gear_ratio_past_u32() {
+        assert_eq!(part2("4000000000*4000000000"), 16_000_000_000_000_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "gear ratio overflowed")]
+    fn gear_ratio_overflow() {
+        let config = SchematicConfig::default().with_gear_parts(3);
+        part2_with_config("4000000000*4000000000\n..........4", &config);
+    }
+
+    #[test]
+    fn render_ansi() {
+        let schematic = "1.2\n.*.\n#..\n..3";
+        let rendered = render(schematic, &SchematicConfig::default(), RenderFormat::Ansi);
+        let expected = concat!(
+            "\x1b[32m1\x1b[0m.\x1b[32m2\x1b[0m\n",
+            ".\x1b[1;33m*\x1b[0m.\n",
+            "#..\n",
+            "..\x1b[31m3\x1b[0m",
+        );
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn render_ansi_bad_gear() {
+        let schematic = "11*";
+        let rendered = render(schematic, &SchematicConfig::default(), RenderFormat::Ansi);
+        assert_eq!(rendered, "\x1b[32m11\x1b[0m\x1b[1;35m*\x1b[0m");
+    }
+
+    #[test]
+    fn render_html() {
+        let schematic = "<5.\n..7";
+        let rendered = render(schematic, &SchematicConfig::default(), RenderFormat::Html);
+        let expected = concat!(
+            "<pre class=\"schematic\">",
+            "&lt;<span class=\"counted-part\">5</span>.\n",
+            "..<span class=\"uncounted-part\">7</span>",
+            "</pre>\n",
+        );
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn render_preserves_schematic() {
+        let rendered = render(EXAMPLE, &SchematicConfig::default(), RenderFormat::Ansi);
+        let mut stripped = String::new();
+        let mut chars = rendered.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().take_while(|c| *c != 'm').for_each(drop);
+            } else {
+                stripped.push(c);
+            }
+        }
+        assert_eq!(stripped, EXAMPLE.trim_end());
+    }
 }
@@ -1,4 +1,5 @@
 use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
 
 pub const EXAMPLE: &str = include_str!("../../inputs/examples/day04.txt");
 pub const REAL: &str = include_str!("../../inputs/real/day04.txt");
@@ -16,37 +17,89 @@ pub fn part1(s: &str) -> usize {
 
 pub fn part2(s: &str) -> usize {
     let table = CardTable::parse(s).expect("invalid input");
-    table.process_scratchcards_with_math()
+    table.validate().expect("invalid table");
+    table.process_scratchcards_linear()
 }
 
+/// A table of scratchcards.
+///
+/// Winning `n` numbers on a card wins a copy of each of the next `n` cards
+/// *in table order*, so ids don't need to be contiguous or sorted. Ids must
+/// be unique, which is checked when the table is built.
+///
+/// Copies that would run past the end of the table are dropped. Use
+/// [`CardTable::validate`] to find out if that happened.
 #[derive(Debug, PartialEq, Eq, Clone)]
-struct CardTable {
+pub struct CardTable {
     cards: BTreeMap<CardId, Card>,
+    order: Vec<CardId>,
+    positions: BTreeMap<CardId, usize>,
     results: BTreeMap<CardId, Vec<CardId>>,
     pending: VecDeque<CardId>,
     processed: usize,
 }
 
 impl CardTable {
-    fn new(cards: impl Iterator<Item = Card>) -> Self {
+    fn new(cards: impl Iterator<Item = Card>) -> Result<Self, String> {
         let mut card_map = BTreeMap::new();
-        let mut pending_stack = VecDeque::new();
+        let mut positions = BTreeMap::new();
+        let mut order = vec![];
 
         for card in cards {
-            card_map.insert(card.id, card.clone());
-            pending_stack.push_back(card.id);
+            if positions.insert(card.id, order.len()).is_some() {
+                return Err(format!("duplicate card id: {}", card.id.0));
+            }
+            order.push(card.id);
+            card_map.insert(card.id, card);
         }
 
-        Self {
+        Ok(Self {
             cards: card_map,
-            pending: pending_stack,
+            pending: order.iter().copied().collect(),
+            order,
+            positions,
             results: BTreeMap::new(),
             processed: 0,
+        })
+    }
+
+    /// Checks that no card wins copies of cards past the end of the table,
+    /// reporting every card that does.
+    pub fn validate(&self) -> Result<(), String> {
+        let overruns: Vec<String> = self
+            .order
+            .iter()
+            .enumerate()
+            .filter_map(|(position, id)| {
+                let wins = self.cards[id].winners().len();
+                let remaining = self.order.len() - position - 1;
+                (wins > remaining).then(|| {
+                    format!(
+                        "card {} wins {wins} copies, but only {remaining} cards follow it",
+                        id.0
+                    )
+                })
+            })
+            .collect();
+
+        if overruns.is_empty() {
+            return Ok(());
         }
+        Err(overruns.join("; "))
+    }
+
+    /// Returns the ids of the cards won by the given card, stopping at the
+    /// end of the table.
+    fn copies_of(&self, id: &CardId) -> &[CardId] {
+        let position = self.positions[id];
+        let wins = self.cards[id].winners().len();
+        let end = (position + 1 + wins).min(self.order.len());
+        &self.order[position + 1..end]
     }
 
-    #[allow(dead_code)]
-    fn process_scratchcards(mut self) -> usize {
+    /// Simulates every copy one at a time. Slow, but obviously correct, so
+    /// it's kept around as an oracle.
+    pub fn process_scratchcards(mut self) -> usize {
         let mut n = 0;
         while self.step().is_some() {
             n += 1;
@@ -55,23 +108,21 @@ impl CardTable {
         self.processed
     }
 
-    // note: cards must be in order and 1-indexed
-    fn process_scratchcards_with_math(self) -> usize {
-        let mut results: BTreeMap<&CardId, HashSet<CardId>> = BTreeMap::new();
+    pub fn process_scratchcards_with_math(self) -> usize {
+        let mut results: Vec<(&CardId, HashSet<CardId>)> = vec![];
 
-        for id in &self.pending {
-            let card = self.cards.get(&id).unwrap();
-            let ids: HashSet<_> = card.id.next_ids(card.winners().len()).into_iter().collect();
-            results.insert(id, ids);
+        for id in &self.order {
+            let ids: HashSet<_> = self.copies_of(id).iter().copied().collect();
+            results.push((id, ids));
         }
 
         let mut total = 0;
         let mut counts: BTreeMap<&CardId, usize> = BTreeMap::new();
 
-        for id in &self.pending {
+        for (position, id) in self.order.iter().enumerate() {
             let count = results
                 .iter()
-                .take_while(|(k, _)| k.0 < id.0)
+                .take(position)
                 .filter(|(_, ids)| ids.contains(id))
                 .map(|(k, _)| counts.get(k).expect("should have been inserted"))
                 .sum::<usize>()
@@ -84,42 +135,180 @@ impl CardTable {
         total
     }
 
+    /// Counts copies by pushing each card's copy count forward to the cards
+    /// it wins, in a single pass over the table.
+    ///
+    /// Every card's count is final by the time we reach it, since copies
+    /// only ever flow to later cards. That makes this O(n·k) for `n` cards
+    /// with at most `k` winners each.
+    pub fn process_scratchcards_linear(&self) -> usize {
+        let mut copies = vec![1usize; self.order.len()];
+
+        let mut total = 0;
+        for (position, id) in self.order.iter().enumerate() {
+            let count = copies[position];
+            total += count;
+
+            let won = self.copies_of(id).len();
+            for n in &mut copies[position + 1..=position + won] {
+                *n += count;
+            }
+        }
+
+        total
+    }
+
+    /// Like [`CardTable::process_scratchcards_linear`], but records where
+    /// every copy came from.
+    ///
+    /// Original cards are generation 1, and copies won by a generation `g`
+    /// card are generation `g + 1`.
+    pub fn trace_scratchcards(&self) -> ScratchcardTrace {
+        let mut rows: Vec<TraceRow> = self
+            .order
+            .iter()
+            .map(|id| TraceRow {
+                id: *id,
+                generations: vec![1],
+                spawned_by: vec![],
+            })
+            .collect();
+
+        for position in 0..rows.len() {
+            let id = rows[position].id;
+            let generations = rows[position].generations.clone();
+            let count: usize = generations.iter().sum();
+
+            let won = self.copies_of(&id).len();
+            for row in &mut rows[position + 1..=position + won] {
+                for (g, n) in generations.iter().enumerate() {
+                    if row.generations.len() <= g + 1 {
+                        row.generations.resize(g + 2, 0);
+                    }
+                    row.generations[g + 1] += n;
+                }
+                row.spawned_by.push((id, count));
+            }
+        }
+
+        ScratchcardTrace { rows }
+    }
+
     pub fn step(&mut self) -> Option<usize> {
-        let id = match self.pending.pop_front() {
-            Some(card) => card,
-            None => return None,
-        };
+        let id = self.pending.pop_front()?;
 
         self.processed += 1;
 
-        let card = self.cards.get(&id).unwrap();
-
         if let Some(ids) = self.results.get(&id) {
             self.pending.extend(ids);
             return Some(ids.len());
         }
 
-        let copies = card.id.next_ids(card.winners().len());
+        let copies = self.copies_of(&id).to_vec();
         self.pending.extend(&copies);
-        self.results.insert(card.id, copies.clone());
+        self.results.insert(id, copies.clone());
         Some(copies.len())
     }
 
-    fn parse(s: &str) -> Result<Self, String> {
+    pub fn parse(s: &str) -> Result<Self, String> {
         let cards = s
             .trim()
             .lines()
             .map(Card::parse)
             .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(Self::new(cards.into_iter()))
+        Self::new(cards.into_iter())
+    }
+}
+
+/// Per-card breakdown of a scratchcard run, from
+/// [`CardTable::trace_scratchcards`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ScratchcardTrace {
+    rows: Vec<TraceRow>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct TraceRow {
+    id: CardId,
+    /// number of copies per generation, starting at generation 1
+    generations: Vec<usize>,
+    /// cards that won copies of this one, and how many copies each won
+    spawned_by: Vec<(CardId, usize)>,
+}
+
+impl TraceRow {
+    fn total(&self) -> usize {
+        self.generations.iter().sum()
+    }
+}
+
+impl ScratchcardTrace {
+    /// Total number of cards, originals included. Matches `part2`.
+    pub fn total(&self) -> usize {
+        self.rows.iter().map(TraceRow::total).sum()
+    }
+
+    /// Renders the trace as a plain text table, one row per card, with a
+    /// column for each generation and a list of which cards spawned copies.
+    pub fn to_table(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for ScratchcardTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let generations = self.rows.iter().map(|r| r.generations.len()).max();
+        let generations = generations.unwrap_or(0);
+
+        let mut header = vec!["card".to_string(), "total".to_string()];
+        header.extend((1..=generations).map(|g| format!("gen {g}")));
+        header.push("spawned by".to_string());
+
+        let mut table = vec![header];
+        for row in &self.rows {
+            let mut cells = vec![row.id.0.to_string(), row.total().to_string()];
+            cells.extend((0..generations).map(|g| {
+                let n = row.generations.get(g).copied().unwrap_or(0);
+                n.to_string()
+            }));
+            let spawned_by: Vec<String> = row
+                .spawned_by
+                .iter()
+                .map(|(id, n)| format!("{}x{n}", id.0))
+                .collect();
+            cells.push(spawned_by.join(", "));
+            table.push(cells);
+        }
+
+        let widths: Vec<usize> = (0..table[0].len())
+            .map(|col| table.iter().map(|row| row[col].len()).max().unwrap_or(0))
+            .collect();
+
+        for row in &table {
+            // numbers are right aligned, the trailing `spawned by` list isn't
+            let last = row.len() - 1;
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(col, (cell, width))| {
+                    if col == last {
+                        cell.to_string()
+                    } else {
+                        format!("{cell:>width$}")
+                    }
+                })
+                .collect();
+            writeln!(f, "{}", cells.join(" | ").trim_end())?;
+        }
+        Ok(())
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct Card {
     id: CardId,
-    generation: usize,
     goal: NumberSet,
     hand: NumberSet,
 }
@@ -141,23 +330,18 @@ impl Card {
     fn parse(s: &str) -> Result<Self, String> {
         let (id, numbers) = s
             .split_once(':')
-            .ok_or_else(|| "failed to parse card: no ':' found")?;
+            .ok_or("failed to parse card: no ':' found")?;
 
         let id = CardId::parse(id)?;
 
         let (goal, hand) = numbers
             .split_once('|')
-            .ok_or_else(|| format!("failed to parse card: no '|' found"))?;
+            .ok_or("failed to parse card: no '|' found")?;
 
         let goal = NumberSet::parse(goal)?;
         let hand = NumberSet::parse(hand)?;
 
-        Ok(Self {
-            id,
-            goal,
-            hand,
-            generation: 1,
-        })
+        Ok(Self { id, goal, hand })
     }
 }
 
@@ -169,10 +353,6 @@ impl CardId {
         Self(n)
     }
 
-    fn next_ids(&self, n: usize) -> Vec<Self> {
-        (self.0 + 1..=self.0 + n).map(Self::new).collect()
-    }
-
     fn parse(s: &str) -> Result<Self, String> {
         s.trim()
             .strip_prefix("Card ")
@@ -202,23 +382,54 @@ impl Number {
             .map(Self::new)
             .map_err(|e| format!("failed to parse number: {e}"))
     }
+
+    /// Returns this number as a single bit in a `u128`, or `None` if it's
+    /// too big to fit.
+    fn bit(&self) -> Option<u128> {
+        1u128.checked_shl(self.0.try_into().ok()?)
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-struct NumberSet(HashSet<Number>);
+/// Set of numbers on one side of a card.
+///
+/// Card numbers are almost always small, so sets where every number fits in
+/// a `u128` are stored as a bitset, where intersection and counting are a
+/// couple of instructions. Anything larger falls back to a `HashSet`.
+#[derive(Debug, Clone)]
+enum NumberSet {
+    Bits(u128),
+    Hashed(HashSet<Number>),
+}
 
 impl NumberSet {
     fn new(set: HashSet<Number>) -> Self {
-        Self(set)
+        let mut bits = 0u128;
+        for n in &set {
+            match n.bit() {
+                Some(bit) => bits |= bit,
+                None => return Self::Hashed(set),
+            }
+        }
+        Self::Bits(bits)
     }
 
     fn empty() -> Self {
-        Self::new(HashSet::new())
+        Self::Bits(0)
     }
 
     fn parse(s: &str) -> Result<Self, String> {
-        let set: Result<HashSet<_>, _> = s.trim().split_whitespace().map(Number::parse).collect();
-        Ok(Self::new(set?))
+        let numbers = s
+            .split_whitespace()
+            .map(Number::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let bits = numbers
+            .iter()
+            .try_fold(0u128, |acc, n| Some(acc | n.bit()?));
+        match bits {
+            Some(bits) => Ok(Self::Bits(bits)),
+            None => Ok(Self::Hashed(numbers.into_iter().collect())),
+        }
     }
 
     #[cfg(test)]
@@ -227,17 +438,55 @@ impl NumberSet {
     }
 
     fn len(&self) -> usize {
-        self.0.len()
+        match self {
+            Self::Bits(bits) => bits.count_ones() as usize,
+            Self::Hashed(set) => set.len(),
+        }
+    }
+
+    fn contains(&self, n: &Number) -> bool {
+        match (self, n.bit()) {
+            (Self::Bits(bits), Some(bit)) => bits & bit != 0,
+            (Self::Bits(_), None) => false,
+            (Self::Hashed(set), _) => set.contains(n),
+        }
+    }
+
+    fn to_hash_set(&self) -> HashSet<Number> {
+        match self {
+            Self::Bits(bits) => (0..u128::BITS as usize)
+                .filter(|i| bits & (1 << i) != 0)
+                .map(Number::new)
+                .collect(),
+            Self::Hashed(set) => set.clone(),
+        }
     }
 
     fn find_winners(&self, other: &Self) -> Self {
-        Self::new(self.0.intersection(&other.0).copied().collect())
+        match (self, other) {
+            (Self::Bits(a), Self::Bits(b)) => Self::Bits(a & b),
+            (Self::Hashed(set), other) | (other, Self::Hashed(set)) => {
+                Self::new(set.iter().filter(|n| other.contains(n)).copied().collect())
+            }
+        }
     }
 }
 
+impl PartialEq for NumberSet {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Bits(a), Self::Bits(b)) => a == b,
+            _ => self.to_hash_set() == other.to_hash_set(),
+        }
+    }
+}
+
+impl Eq for NumberSet {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn part1_example() {
@@ -260,8 +509,13 @@ mod tests {
     }
 
     #[test]
-    fn card_ids_next_ids() {
-        assert_eq!(CardId(1).next_ids(3), vec![CardId(2), CardId(3), CardId(4)]);
+    fn card_table_copies_of() {
+        let table = CardTable::parse(EXAMPLE).expect("valid input");
+        assert_eq!(
+            table.copies_of(&CardId(1)),
+            &[CardId(2), CardId(3), CardId(4), CardId(5)]
+        );
+        assert_eq!(table.copies_of(&CardId(6)), &[]);
     }
 
     #[test]
@@ -329,6 +583,149 @@ Card 2: 18 25 71 33 19 | 71 33 19 25  6 18  3  4
             .map(Number::new)
             .collect();
         assert_eq!(numbers.len(), 5);
-        assert_eq!(numbers, NumberSet(expected));
+        assert_eq!(numbers, NumberSet::new(expected));
+    }
+
+    #[test]
+    fn process_scratchcards_oracles() {
+        let table = CardTable::parse(EXAMPLE).expect("valid input");
+        assert_eq!(table.process_scratchcards_linear(), 30);
+        assert_eq!(table.clone().process_scratchcards_with_math(), 30);
+        assert_eq!(table.process_scratchcards(), 30);
+    }
+
+    #[test]
+    fn process_scratchcards_linear_real() {
+        let table = CardTable::parse(REAL).expect("valid input");
+        assert_eq!(
+            table.process_scratchcards_linear(),
+            table.process_scratchcards_with_math()
+        );
+    }
+
+    #[test]
+    fn card_table_past_end() {
+        // the last card wins copies of cards that don't exist
+        let input = "
+Card 1: 1 2 | 1 3
+Card 2: 1 2 | 1 2
+        ";
+        let table = CardTable::parse(input).expect("valid input");
+        let err = table.validate().expect_err("past end of table");
+        assert_eq!(err, "card 2 wins 2 copies, but only 0 cards follow it");
+
+        // copies past the end are dropped rather than miscounted
+        assert_eq!(table.process_scratchcards_linear(), 3);
+        assert_eq!(table.clone().process_scratchcards_with_math(), 3);
+        assert_eq!(table.process_scratchcards(), 3);
+    }
+
+    #[test]
+    fn card_table_duplicate_ids() {
+        let input = "
+Card 1: 1 2 | 3 4
+Card 1: 1 2 | 3 4
+        ";
+        let err = CardTable::parse(input).expect_err("duplicate id");
+        assert_eq!(err, "duplicate card id: 1");
+    }
+
+    #[test]
+    fn card_table_gaps_and_unordered() {
+        // same shape as the example, but with shuffled, sparse ids. copies
+        // follow table order, so the total is unchanged.
+        let input = EXAMPLE
+            .lines()
+            .zip([40, 7, 100, 3, 12, 1])
+            .map(|(line, id)| {
+                let (_, rest) = line.split_once(':').unwrap();
+                format!("Card {id}:{rest}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let table = CardTable::parse(&input).expect("valid input");
+        assert_eq!(table.copies_of(&CardId(7)), &[CardId(100), CardId(3)]);
+        assert_eq!(table.process_scratchcards_linear(), 30);
+        assert_eq!(table.clone().process_scratchcards_with_math(), 30);
+        assert_eq!(table.process_scratchcards(), 30);
+    }
+
+    #[test]
+    fn number_set_representation() {
+        assert!(matches!(
+            NumberSet::must_parse("0 1 127"),
+            NumberSet::Bits(_)
+        ));
+        assert!(matches!(
+            NumberSet::must_parse("1 128"),
+            NumberSet::Hashed(_)
+        ));
+    }
+
+    #[test]
+    fn number_set_mixed_winners() {
+        let small = NumberSet::must_parse("1 2 3 99");
+        let large = NumberSet::must_parse("3 99 500");
+        let winners = small.find_winners(&large);
+        assert_eq!(winners, NumberSet::must_parse("3 99"));
+        assert_eq!(winners, large.find_winners(&small));
+        assert!(matches!(winners, NumberSet::Bits(_)));
+    }
+
+    proptest! {
+        #[test]
+        fn number_set_representations_agree(
+            goal in prop::collection::hash_set(0..200usize, 0..20),
+            hand in prop::collection::hash_set(0..200usize, 0..30),
+        ) {
+            let to_set = |v: &HashSet<usize>| v.iter().copied().map(Number::new).collect::<HashSet<_>>();
+            let (goal, hand) = (to_set(&goal), to_set(&hand));
+
+            let hashed = NumberSet::Hashed(goal.clone()).find_winners(&NumberSet::Hashed(hand.clone()));
+            let compact = NumberSet::new(goal.clone()).find_winners(&NumberSet::new(hand.clone()));
+            let expected: HashSet<_> = goal.intersection(&hand).copied().collect();
+
+            assert_eq!(hashed.len(), expected.len());
+            assert_eq!(compact.len(), expected.len());
+            assert_eq!(hashed.to_hash_set(), expected);
+            assert_eq!(compact.to_hash_set(), expected);
+            assert_eq!(hashed, compact);
+        }
+    }
+
+    #[test]
+    fn trace_scratchcards_example() {
+        let table = CardTable::parse(EXAMPLE).expect("valid input");
+        let trace = table.trace_scratchcards();
+        assert_eq!(trace.total(), 30);
+
+        let row = &trace.rows[3];
+        assert_eq!(row.id, CardId(4));
+        assert_eq!(row.generations, vec![1, 3, 3, 1]);
+        assert_eq!(
+            row.spawned_by,
+            vec![(CardId(1), 1), (CardId(2), 2), (CardId(3), 4)]
+        );
+
+        let expected = "\
+card | total | gen 1 | gen 2 | gen 3 | gen 4 | gen 5 | spawned by
+   1 |     1 |     1 |     0 |     0 |     0 |     0 |
+   2 |     2 |     1 |     1 |     0 |     0 |     0 | 1x1
+   3 |     4 |     1 |     2 |     1 |     0 |     0 | 1x1, 2x2
+   4 |     8 |     1 |     3 |     3 |     1 |     0 | 1x1, 2x2, 3x4
+   5 |    14 |     1 |     3 |     5 |     4 |     1 | 1x1, 3x4, 4x8
+   6 |     1 |     1 |     0 |     0 |     0 |     0 |
+";
+        assert_eq!(trace.to_table(), expected);
+    }
+
+    #[test]
+    fn trace_scratchcards_real() {
+        let table = CardTable::parse(REAL).expect("valid input");
+        assert_eq!(
+            table.trace_scratchcards().total(),
+            table.process_scratchcards_linear()
+        );
     }
 }
@@ -1,4 +1,7 @@
-use std::{cmp::Ordering, collections::BTreeMap, ops::Index};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BTreeMap,
+};
 
 pub const EXAMPLE: &str = include_str!("../../inputs/examples/day07.txt");
 pub const REAL: &str = include_str!("../../inputs/real/day07.txt");
@@ -11,247 +14,309 @@ pub fn part2(s: &str) -> usize {
     CardTable::parse_with_jokers(s).winnings()
 }
 
-// this is an odd representation but it makes visually
-// parsing some of the logic easier when trying to figure
-// out if a hand type is valid against a given set of jokers
-enum Jokers {
-    _____,
-    J____,
-    JJ___,
-    JJJ__,
-    JJJJ_,
-    JJJJJ,
-}
-
-impl Jokers {
-    fn from(n: usize) -> Self {
-        match n {
-            0 => Self::_____,
-            1 => Self::J____,
-            2 => Self::JJ___,
-            3 => Self::JJJ__,
-            4 => Self::JJJJ_,
-            5 => Self::JJJJJ,
-            _ => panic!("invalid number of jokers: {n}"),
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-struct MatchCounter(BTreeMap<usize, usize>);
-
-impl MatchCounter {
-    fn new() -> Self {
-        Self(BTreeMap::new())
-    }
+/// Rules for a game of Camel Cards: what the cards are, how they rank, which
+/// card is wild, and how hand types rank against each other.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Ruleset {
+    /// what the `J` label parses as
+    j: Card,
 
-    fn add(&mut self, n: usize) {
-        *self.0.entry(n).or_default() += 1;
-    }
-}
+    /// cards from weakest to strongest, used to break ties
+    order: Vec<Card>,
 
-impl Index<usize> for MatchCounter {
-    type Output = usize;
+    /// card that counts as whatever makes the best hand type
+    wild: Option<Card>,
 
-    fn index(&self, count: usize) -> &Self::Output {
-        self.0.get(&count).unwrap_or(&0)
-    }
-}
+    /// hand types from weakest to strongest
+    precedence: Vec<HandType>,
 
-#[derive(Debug, PartialEq, Eq, Ord, Hash, Clone)]
-pub struct Hand {
-    first: Card,
-    second: Card,
-    third: Card,
-    fourth: Card,
-    fifth: Card,
-    type_: Option<HandType>,
+    /// number of cards in a hand
+    hand_size: usize,
 }
 
-impl Hand {
-    pub fn new(first: Card, second: Card, third: Card, fourth: Card, fifth: Card) -> Self {
+impl Ruleset {
+    /// Creates a ruleset. `order` and `precedence` go from weakest to
+    /// strongest, and `J` labels parse as `j`.
+    pub fn new(j: Card, order: Vec<Card>, wild: Option<Card>, precedence: Vec<HandType>) -> Self {
         Self {
-            first,
-            second,
-            third,
-            fourth,
-            fifth,
-            type_: None,
+            j,
+            order,
+            wild,
+            precedence,
+            hand_size: 5,
         }
     }
 
-    pub fn parse(s: &str) -> Self {
-        Self::inner_parse(s, Card::parse)
+    /// Sets the number of cards in a hand, five by default.
+    pub fn with_hand_size(mut self, hand_size: usize) -> Self {
+        assert!(hand_size > 0, "hands need at least one card");
+        self.hand_size = hand_size;
+        self
     }
 
-    pub fn parse_with_jokers(s: &str) -> Self {
-        Self::inner_parse(s, Card::parse_with_jokers)
+    /// Part 1 rules: `J` is a jack, nothing is wild.
+    pub fn standard() -> Self {
+        use Card::*;
+        Self::new(
+            Jack,
+            vec![
+                Two, Three, Four, Five, Six, Seven, Eight, Nine, Ten, Jack, Queen, King, Ace,
+            ],
+            None,
+            HandType::ALL.to_vec(),
+        )
     }
 
-    fn inner_parse(s: &str, builder: fn(char) -> Card) -> Self {
-        let mut this = {
-            let mut cards = s.chars();
-            let first = builder(cards.next().unwrap());
-            let second = builder(cards.next().unwrap());
-            let third = builder(cards.next().unwrap());
-            let fourth = builder(cards.next().unwrap());
-            let fifth = builder(cards.next().unwrap());
+    /// Part 2 rules: `J` is a joker, which is wild but the weakest card
+    /// when breaking ties.
+    pub fn jokers() -> Self {
+        use Card::*;
+        Self::new(
+            Joker,
+            vec![
+                Joker, Two, Three, Four, Five, Six, Seven, Eight, Nine, Ten, Queen, King, Ace,
+            ],
+            Some(Joker),
+            HandType::ALL.to_vec(),
+        )
+    }
 
-            if let Some(c) = cards.next() {
-                panic!("too many cards, found trailing {c}");
-            }
+    fn parse_card(&self, c: char) -> Card {
+        let card = Card::inner_parse(c, self.j);
+        assert!(self.order.contains(&card), "invalid card for ruleset: {c}");
+        card
+    }
 
-            Self::new(first, second, third, fourth, fifth)
-        };
-        this.pre_cache_type();
-        this
+    /// Returns the strength of a card for tie-breaking, higher is stronger.
+    fn card_rank(&self, card: Card) -> u8 {
+        self.order
+            .iter()
+            .position(|c| *c == card)
+            .unwrap_or_else(|| panic!("card not in ruleset: {card:?}")) as u8
     }
 
-    fn cards(&self) -> [Card; 5] {
-        [self.first, self.second, self.third, self.fourth, self.fifth]
+    /// Returns the strength of a hand type, higher is stronger.
+    fn type_rank(&self, type_: HandType) -> u8 {
+        self.try_type_rank(type_)
+            .unwrap_or_else(|| panic!("hand type not in ruleset: {type_:?}"))
     }
 
-    fn pre_cache_type(&mut self) {
-        self.type_ = Some(self.get_type());
+    fn try_type_rank(&self, type_: HandType) -> Option<u8> {
+        self.precedence
+            .iter()
+            .position(|t| *t == type_)
+            .map(|rank| rank as u8)
     }
 
-    fn get_type(&self) -> HandType {
-        if let Some(type_) = self.type_ {
-            return type_;
-        }
+    /// Classifies a hand.
+    fn classify(&self, cards: &[Card]) -> HandType {
+        self.substitute(cards).1
+    }
 
-        // keep track of how many times we see each card. we'll need
-        // to be able to pull out jokers specifically, but otherwise
-        // the count will be more important than the card itself.
+    /// Works out what each wild card should become to give the strongest
+    /// type under this ruleset, and returns the cards after substituting
+    /// them along with that type.
+    ///
+    /// With the usual precedence, every wild card joining the largest group
+    /// is always best. A custom precedence can make it better to split them
+    /// up or start a new group, so every way of handing out the wild cards
+    /// is tried. Ties go to the first one tried, which favours bigger groups
+    /// and then stronger cards.
+    fn substitute(&self, cards: &[Card]) -> (Vec<Card>, HandType) {
         let mut seen: BTreeMap<Card, usize> = BTreeMap::new();
-        for card in self.cards() {
-            *seen.entry(card).or_default() += 1;
+        let mut wilds = 0;
+        for card in cards {
+            if Some(*card) == self.wild {
+                wilds += 1;
+            } else {
+                *seen.entry(*card).or_default() += 1;
+            }
         }
 
-        let jokers = {
-            let n = *seen.entry(Card::Joker).or_default();
-            Jokers::from(n)
-        };
-
-        // we want a map that answers "how many times do we see `n`
-        // matching cards?", so if we had a FiveOfAKind, we'd have
-        // a map with a single entry of (5, 1). if we had a FullHouse,
-        // we'd have a map with two entries of (2, 1) and (3, 1).
-        let mut count = MatchCounter::new();
-        for (_, n) in seen {
-            count.add(n);
+        if wilds == 0 {
+            let mut groups: Vec<usize> = seen.into_values().collect();
+            groups.sort_unstable_by(|a, b| b.cmp(a));
+            return (cards.to_vec(), HandType::from_groups(&groups));
         }
 
-        use HandType::*;
-        use Jokers::*;
-
-        if count[1] == 5 {
-            return match jokers {
-                _____ => HighCard,
-                J____ => OnePair,
-                JJ___ => panic!("impossible: 2 jokers, HighCard"),
-                JJJ__ => panic!("impossible: 3 jokers, HighCard"),
-                JJJJ_ => panic!("impossible: 4 jokers, HighCard"),
-                JJJJJ => panic!("impossible: 5 jokers, HighCard"),
-            };
+        // what wild cards can become: the groups already in the hand,
+        // biggest and strongest first, then other cards, strongest first
+        let mut targets: Vec<(Card, usize)> = seen.iter().map(|(c, n)| (*c, *n)).collect();
+        targets.sort_by_key(|(card, count)| Reverse((*count, self.card_rank(*card))));
+        let present = targets.len();
+        targets.extend(
+            self.order
+                .iter()
+                .rev()
+                .filter(|c| Some(**c) != self.wild && !seen.contains_key(c))
+                .map(|c| (*c, 0)),
+        );
+
+        if targets.is_empty() {
+            // nothing for the wild cards to become, so they stay wild
+            return (cards.to_vec(), HandType::from_groups(&[wilds]));
         }
 
-        if count[2] == 1 && count[1] == 3 {
-            return match jokers {
-                _____ => OnePair,
-                J____ => ThreeOfAKind,
-                JJ___ => ThreeOfAKind,
-                JJJ__ => panic!("impossible: 3 jokers, OnePair"),
-                JJJJ_ => panic!("impossible: 4 jokers, OnePair"),
-                JJJJJ => panic!("impossible: 5 jokers, OnePair"),
-            };
-        }
+        let mut counts: Vec<usize> = targets.iter().map(|(_, n)| *n).collect();
+        let mut best = None;
+        self.place_wilds(&mut counts, present, wilds, 0, &mut vec![], &mut best);
+        let (_, type_, picks) =
+            best.unwrap_or_else(|| panic!("no hand type in ruleset for {cards:?}"));
+
+        let mut picks = picks.into_iter();
+        let cards = cards
+            .iter()
+            .map(|c| {
+                if Some(*c) == self.wild {
+                    picks.next().map_or(*c, |pick| targets[pick].0)
+                } else {
+                    *c
+                }
+            })
+            .collect();
+        (cards, type_)
+    }
 
-        if count[2] == 2 && count[1] == 1 {
-            return match jokers {
-                _____ => TwoPair,
-                J____ => FullHouse,
-                JJ___ => FourOfAKind,
-                JJJ__ => panic!("impossible: 3 jokers, TwoPair"),
-                JJJJ_ => panic!("impossible: 4 jokers, TwoPair"),
-                JJJJJ => panic!("impossible: 5 jokers, TwoPair"),
-            };
+    /// Tries every way of adding `wilds` more cards to `counts`, keeping the
+    /// strongest type found in `best` along with which target each wild
+    /// card went to. Targets are picked in order, so each combination is
+    /// only tried once.
+    fn place_wilds(
+        &self,
+        counts: &mut [usize],
+        present: usize,
+        wilds: usize,
+        start: usize,
+        picks: &mut Vec<usize>,
+        best: &mut Option<(u8, HandType, Vec<usize>)>,
+    ) {
+        if wilds == 0 {
+            let mut groups: Vec<usize> = counts.iter().copied().filter(|n| *n > 0).collect();
+            groups.sort_unstable_by(|a, b| b.cmp(a));
+            let type_ = HandType::from_groups(&groups);
+            if let Some(rank) = self.try_type_rank(type_) {
+                if best.as_ref().is_none_or(|(best, _, _)| rank > *best) {
+                    *best = Some((rank, type_, picks.clone()));
+                }
+            }
+            return;
         }
 
-        if count[3] == 1 && count[1] == 2 {
-            return match jokers {
-                _____ => ThreeOfAKind,
-                J____ => FourOfAKind,
-                JJ___ => panic!("impossible: 2 jokers, ThreeOfAKind"),
-                JJJ__ => FourOfAKind,
-                JJJJ_ => FiveOfAKind,
-                JJJJJ => panic!("impossible: 5 jokers, ThreeOfAKind"),
-            };
+        for i in start..counts.len() {
+            // cards not in the hand are interchangeable, so only ever start
+            // on the first one that's still unused
+            if i > present && counts[i - 1] == 0 {
+                break;
+            }
+            counts[i] += 1;
+            picks.push(i);
+            self.place_wilds(counts, present, wilds - 1, i, picks, best);
+            picks.pop();
+            counts[i] -= 1;
         }
+    }
 
-        if count[3] == 1 && count[2] == 1 {
-            return match jokers {
-                _____ => FullHouse,
-                J____ => panic!("impossible: 1 joker, FullHouse"),
-                JJ___ => FiveOfAKind,
-                JJJ__ => FiveOfAKind,
-                JJJJ_ => panic!("impossible: 4 jokers, FullHouse"),
-                JJJJJ => panic!("impossible: 5 jokers, FullHouse"),
-            };
+    /// Returns the cards a hand plays as once its wild cards are replaced,
+    /// along with the type that gives.
+    ///
+    /// Usually every wild card becomes a copy of the card in the largest
+    /// group, so `KTJJT` plays as `KTTTT`. When groups are tied, or the
+    /// whole hand is wild, the wild cards become the strongest candidate.
+    pub fn best_substitution(&self, hand: &Hand) -> (Vec<Card>, HandType) {
+        if self.wild.is_none() {
+            return (hand.cards.clone(), hand.type_);
         }
+        self.substitute(&hand.cards)
+    }
+}
 
-        if count[4] == 1 && count[1] == 1 {
-            return match jokers {
-                _____ => FourOfAKind,
-                J____ => FiveOfAKind,
-                JJ___ => panic!("impossible: 2 jokers, FourOfAKind"),
-                JJJ__ => panic!("impossible: 3 jokers, FourOfAKind"),
-                JJJJ_ => FiveOfAKind,
-                JJJJJ => panic!("impossible: 5 jokers, FourOfAKind"),
-            };
-        }
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Hand {
+    cards: Vec<Card>,
+    type_: HandType,
 
-        if count[5] == 1 {
-            return FiveOfAKind;
-        }
+    // ruleset-specific strengths, precomputed so comparing hands doesn't
+    // need the ruleset
+    type_rank: u8,
+    ranks: Vec<u8>,
+}
 
-        unreachable!("impossible: {:?}", self);
+impl Hand {
+    /// Creates a five card hand under the standard (part 1) rules.
+    pub fn new(first: Card, second: Card, third: Card, fourth: Card, fifth: Card) -> Self {
+        let cards = vec![first, second, third, fourth, fifth];
+        Self::with_rules(cards, &Ruleset::standard())
     }
-}
 
-impl PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let ord = self.get_type().partial_cmp(&other.get_type());
-        if ord != Some(Ordering::Equal) {
-            return ord;
+    /// Creates a hand under the given rules.
+    ///
+    /// Panics if the hand isn't the size the rules call for.
+    pub fn with_rules(cards: Vec<Card>, rules: &Ruleset) -> Self {
+        assert_eq!(
+            cards.len(),
+            rules.hand_size,
+            "expected {} cards, found {}",
+            rules.hand_size,
+            cards.len()
+        );
+        let type_ = rules.classify(&cards);
+        Self {
+            type_,
+            type_rank: rules.type_rank(type_),
+            ranks: cards.iter().map(|c| rules.card_rank(*c)).collect(),
+            cards,
         }
+    }
 
-        let ord = self.first.cmp(&other.first);
-        if ord != Ordering::Equal {
-            return Some(ord);
-        }
+    pub fn parse(s: &str) -> Self {
+        Self::parse_with_rules(s, &Ruleset::standard())
+    }
 
-        let ord = self.second.cmp(&other.second);
-        if ord != Ordering::Equal {
-            return Some(ord);
-        }
+    pub fn parse_with_jokers(s: &str) -> Self {
+        Self::parse_with_rules(s, &Ruleset::jokers())
+    }
 
-        let ord = self.third.cmp(&other.third);
-        if ord != Ordering::Equal {
-            return Some(ord);
-        }
+    pub fn parse_with_rules(s: &str, rules: &Ruleset) -> Self {
+        let cards = s.chars().map(|c| rules.parse_card(c)).collect();
+        Self::with_rules(cards, rules)
+    }
 
-        let ord = self.fourth.cmp(&other.fourth);
-        if ord != Ordering::Equal {
-            return Some(ord);
-        }
+    pub fn get_type(&self) -> HandType {
+        self.type_
+    }
 
-        let ord = self.fifth.cmp(&other.fifth);
-        if ord != Ordering::Equal {
-            return Some(ord);
-        }
+    /// Returns the hand packed into a single integer that sorts the same way
+    /// hands do: the type's strength in the high bits, then four bits per
+    /// card from left to right.
+    ///
+    /// Only comparable between hands of the same size and ruleset. Panics
+    /// for hands of more than 15 cards, which don't fit.
+    pub fn key(&self) -> u64 {
+        assert!(self.ranks.len() < 16, "hand too big for a key");
+        self.ranks
+            .iter()
+            .fold(u64::from(self.type_rank), |key, rank| {
+                key << 4 | u64::from(*rank)
+            })
+    }
+}
 
-        Some(Ordering::Equal)
+impl Ord for Hand {
+    /// Hands are ordered by type first, then card by card from left to
+    /// right, using the strengths from the hand's ruleset.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.type_rank
+            .cmp(&other.type_rank)
+            .then_with(|| self.ranks.cmp(&other.ranks))
+            // only reachable when comparing hands from different rulesets,
+            // but it keeps `Ord` consistent with `Eq`
+            .then_with(|| self.cards.cmp(&other.cards))
+    }
+}
+
+impl PartialOrd for Hand {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -274,14 +339,6 @@ pub enum Card {
 }
 
 impl Card {
-    fn parse_with_jokers(c: char) -> Self {
-        Self::inner_parse(c, Self::Joker)
-    }
-
-    fn parse(c: char) -> Self {
-        Self::inner_parse(c, Self::Jack)
-    }
-
     fn inner_parse(c: char, j: Self) -> Self {
         match c {
             '2' => Self::Two,
@@ -302,12 +359,12 @@ impl Card {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 struct Bet(usize);
 
 impl Bet {
     fn parse(s: &str) -> Self {
-        Self(s.parse().expect(&format!("invalid bet {s}")))
+        Self(s.parse().unwrap_or_else(|_| panic!("invalid bet {s}")))
     }
 }
 
@@ -319,27 +376,25 @@ struct HandBet {
 
 impl PartialOrd for HandBet {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.hand.partial_cmp(&other.hand)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for HandBet {
+    /// Ordered by hand. Equal hands fall back to the bet so the order stays
+    /// consistent with `Eq`; it doesn't matter for winnings since equal
+    /// hands can't be ranked against each other anyway.
     fn cmp(&self, other: &Self) -> Ordering {
-        self.hand.cmp(&other.hand)
+        self.hand
+            .cmp(&other.hand)
+            .then_with(|| self.bet.cmp(&other.bet))
     }
 }
 
 impl HandBet {
-    fn parse(s: &str) -> Self {
+    fn parse_with_rules(s: &str, rules: &Ruleset) -> Self {
         let mut parts = s.split_whitespace();
-        let hand = Hand::parse(parts.next().expect("missing hand"));
-        let bet = Bet::parse(parts.next().expect("missing bet"));
-        Self { hand, bet }
-    }
-
-    fn parse_with_jokers(s: &str) -> Self {
-        let mut parts = s.split_whitespace();
-        let hand = Hand::parse_with_jokers(parts.next().expect("missing hand"));
+        let hand = Hand::parse_with_rules(parts.next().expect("missing hand"), rules);
         let bet = Bet::parse(parts.next().expect("missing bet"));
         Self { hand, bet }
     }
@@ -350,22 +405,49 @@ impl HandBet {
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
-struct CardTable {
+pub struct CardTable {
     hands: Vec<HandBet>,
 }
 
 impl CardTable {
-    fn parse(s: &str) -> Self {
-        let hands = s.lines().map(HandBet::parse).collect();
-        Self { hands }
+    pub fn parse(s: &str) -> Self {
+        Self::parse_with_rules(s, &Ruleset::standard())
     }
 
     pub fn parse_with_jokers(s: &str) -> Self {
-        let hands = s.lines().map(HandBet::parse_with_jokers).collect();
+        Self::parse_with_rules(s, &Ruleset::jokers())
+    }
+
+    pub fn parse_with_rules(s: &str, rules: &Ruleset) -> Self {
+        let hands = s
+            .lines()
+            .map(|line| HandBet::parse_with_rules(line, rules))
+            .collect();
         Self { hands }
     }
 
-    fn winnings(&mut self) -> usize {
+    /// Returns the total winnings: each bet times the rank of its hand, the
+    /// weakest hand being rank 1.
+    ///
+    /// Sorts packed [`Hand::key`]s rather than the hands themselves, which
+    /// is much cheaper than comparing hands card by card.
+    pub fn winnings(&self) -> usize {
+        let mut keys: Vec<(u64, usize)> = self
+            .hands
+            .iter()
+            .map(|hand| (hand.hand.key(), hand.bet.0))
+            .collect();
+        keys.sort_unstable();
+
+        keys.iter()
+            .zip(1..)
+            .map(|((_, bet), rank)| bet * rank)
+            .sum()
+    }
+
+    /// Returns the same total as [`CardTable::winnings`], sorting the hands
+    /// themselves.
+    pub fn winnings_by_comparison(&mut self) -> usize {
         let mut rank = self.hands.len();
         self.hands.sort();
         self.hands.reverse();
@@ -380,8 +462,129 @@ impl CardTable {
     }
 }
 
+/// Statistics over the hands a ruleset allows, either every one of them or a
+/// random sample.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct HandStats {
+    /// weakest first
+    hands: Vec<Hand>,
+}
+
+impl HandStats {
+    /// Builds stats from every possible hand under the rules. There are
+    /// `cards ^ hand_size` of them, 371,293 for the standard rules, so for
+    /// bigger hands [`HandStats::sample`] is the better choice.
+    pub fn enumerate(rules: &Ruleset) -> Self {
+        let base = rules.order.len();
+        let total = u32::try_from(rules.hand_size)
+            .ok()
+            .and_then(|size| base.checked_pow(size))
+            .expect("too many hands to enumerate");
+
+        let mut hands = Vec::with_capacity(total);
+        let mut digits = vec![0; rules.hand_size];
+        for _ in 0..total {
+            let cards = digits.iter().map(|&d| rules.order[d]).collect();
+            hands.push(Hand::with_rules(cards, rules));
+
+            // count up in base `cards`, last card fastest
+            for digit in digits.iter_mut().rev() {
+                *digit += 1;
+                if *digit < base {
+                    break;
+                }
+                *digit = 0;
+            }
+        }
+
+        Self::from_hands(hands)
+    }
+
+    /// Builds stats from `count` hands drawn uniformly at random, with
+    /// replacement. The same seed always draws the same hands.
+    pub fn sample(rules: &Ruleset, count: usize, seed: u64) -> Self {
+        let mut rng = SplitMix64(seed);
+        let hands = (0..count)
+            .map(|_| {
+                let cards = (0..rules.hand_size)
+                    .map(|_| rules.order[rng.below(rules.order.len())])
+                    .collect();
+                Hand::with_rules(cards, rules)
+            })
+            .collect();
+        Self::from_hands(hands)
+    }
+
+    fn from_hands(mut hands: Vec<Hand>) -> Self {
+        hands.sort_unstable();
+        Self { hands }
+    }
+
+    /// Returns the number of hands the stats were built from.
+    pub fn len(&self) -> usize {
+        self.hands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hands.is_empty()
+    }
+
+    /// Returns how many hands there are of each type. Types with no hands
+    /// are left out.
+    pub fn type_distribution(&self) -> BTreeMap<HandType, usize> {
+        let mut counts = BTreeMap::new();
+        for hand in &self.hands {
+            *counts.entry(hand.get_type()).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Returns the fraction of hands the given hand beats, counting a tie
+    /// as half a win. Between 0 and 1, or NaN if there are no hands.
+    fn win_rate(&self, hand: &Hand) -> f64 {
+        let weaker = self.hands.partition_point(|h| h < hand);
+        let tied = self.hands[weaker..].partition_point(|h| h == hand);
+        (weaker as f64 + tied as f64 / 2.0) / self.len() as f64
+    }
+
+    /// Returns the percentile rank of a hand: the percentage of hands it
+    /// beats, counting ties as half.
+    pub fn percentile(&self, hand: &Hand) -> f64 {
+        self.win_rate(hand) * 100.0
+    }
+
+    /// Returns the expected winnings of betting on `hand` at a table with
+    /// `field` other hands drawn from these stats.
+    ///
+    /// The hand's rank is one more than the number of other hands it beats,
+    /// so on average it's `1 + field * win_rate`. Ties could go either way
+    /// and count as half.
+    pub fn expected_winnings(&self, hand: &Hand, bet: usize, field: usize) -> f64 {
+        bet as f64 * (1.0 + field as f64 * self.win_rate(hand))
+    }
+}
+
+/// Small, fast, seedable random number generator, plenty for sampling hands.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`. The modulo bias is negligible for the
+    /// handful of cards in a ruleset.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
-enum HandType {
+pub enum HandType {
     HighCard,
     OnePair,
     TwoPair,
@@ -389,11 +592,54 @@ enum HandType {
     FullHouse,
     FourOfAKind,
     FiveOfAKind,
+    SixOfAKind,
+    SevenOfAKind,
+    EightOfAKind,
+}
+
+impl HandType {
+    /// Every hand type, from weakest to strongest.
+    pub const ALL: [HandType; 10] = [
+        HandType::HighCard,
+        HandType::OnePair,
+        HandType::TwoPair,
+        HandType::ThreeOfAKind,
+        HandType::FullHouse,
+        HandType::FourOfAKind,
+        HandType::FiveOfAKind,
+        HandType::SixOfAKind,
+        HandType::SevenOfAKind,
+        HandType::EightOfAKind,
+    ];
+
+    /// Returns the hand type for the given group sizes, largest first. A
+    /// full house would be `[3, 2]`, and two pair would be `[2, 2, 1]`.
+    ///
+    /// Hands bigger than five cards are classified by their two largest
+    /// groups, so `[3, 3]` is a full house and `[2, 2, 2]` is two pair.
+    /// Groups bigger than eight count as eight of a kind.
+    fn from_groups(groups: &[usize]) -> Self {
+        use HandType::*;
+        match groups {
+            [n, ..] if *n >= 8 => EightOfAKind,
+            [7, ..] => SevenOfAKind,
+            [6, ..] => SixOfAKind,
+            [5, ..] => FiveOfAKind,
+            [4, ..] => FourOfAKind,
+            [3, n, ..] if *n >= 2 => FullHouse,
+            [3, ..] => ThreeOfAKind,
+            [2, 2, ..] => TwoPair,
+            [2, ..] => OnePair,
+            _ => HighCard,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashMap;
 
     #[test]
     fn part1_real() {
@@ -407,7 +653,7 @@ mod tests {
 
     #[test]
     fn card_table_winnings_jokers() {
-        let mut table = CardTable::parse_with_jokers(EXAMPLE);
+        let table = CardTable::parse_with_jokers(EXAMPLE);
         let result = table.winnings();
         let expected = 5905;
         assert_eq!(result, expected);
@@ -415,7 +661,7 @@ mod tests {
 
     #[test]
     fn card_table_winnings() {
-        let mut table = CardTable::parse(EXAMPLE);
+        let table = CardTable::parse(EXAMPLE);
         let result = table.winnings();
         let expected = 6440;
         assert_eq!(result, expected);
@@ -519,4 +765,407 @@ mod tests {
         let expected = HandType::HighCard;
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn ruleset_jokers_join_largest_group() {
+        let cases = [
+            ("23456", HandType::HighCard),
+            ("2345J", HandType::OnePair),
+            ("2234J", HandType::ThreeOfAKind),
+            ("223JJ", HandType::FourOfAKind),
+            ("2233J", HandType::FullHouse),
+            ("222JJ", HandType::FiveOfAKind),
+            ("2JJJJ", HandType::FiveOfAKind),
+            ("JJJJJ", HandType::FiveOfAKind),
+            ("KTJJT", HandType::FourOfAKind),
+        ];
+        for (hand, expected) in cases {
+            assert_eq!(Hand::parse_with_jokers(hand).get_type(), expected, "{hand}");
+        }
+    }
+
+    #[test]
+    fn ruleset_custom_wild() {
+        use Card::*;
+        let aces_wild = Ruleset::new(
+            Jack,
+            vec![
+                Ace, Two, Three, Four, Five, Six, Seven, Eight, Nine, Ten, Jack, Queen, King,
+            ],
+            Some(Ace),
+            HandType::ALL.to_vec(),
+        );
+
+        let hand = Hand::parse_with_rules("KAQAK", &aces_wild);
+        assert_eq!(hand.get_type(), HandType::FourOfAKind);
+
+        // aces are the weakest card for tie breaks under these rules
+        let weaker = Hand::parse_with_rules("A2345", &aces_wild);
+        let stronger = Hand::parse_with_rules("22345", &aces_wild);
+        assert_eq!(weaker.get_type(), stronger.get_type());
+        assert!(weaker < stronger);
+    }
+
+    #[test]
+    fn ruleset_custom_precedence() {
+        use HandType::*;
+        let rules = Ruleset::new(
+            Card::Jack,
+            Ruleset::standard().order,
+            None,
+            vec![
+                HighCard,
+                OnePair,
+                ThreeOfAKind,
+                TwoPair,
+                FullHouse,
+                FourOfAKind,
+                FiveOfAKind,
+            ],
+        );
+
+        let two_pair = Hand::parse_with_rules("22334", &rules);
+        let three = Hand::parse_with_rules("AAAKQ", &rules);
+        assert!(two_pair > three);
+
+        let table = "22334 1\nAAAKQ 10";
+        assert_eq!(CardTable::parse(table).winnings(), 1 + 10 * 2);
+        assert_eq!(
+            CardTable::parse_with_rules(table, &rules).winnings(),
+            2 + 10
+        );
+    }
+
+    #[test]
+    fn ruleset_wild_custom_precedence() {
+        use HandType::*;
+        let rules = Ruleset::new(
+            Card::Joker,
+            Ruleset::jokers().order,
+            Some(Card::Joker),
+            vec![
+                HighCard,
+                OnePair,
+                ThreeOfAKind,
+                TwoPair,
+                FullHouse,
+                FourOfAKind,
+                FiveOfAKind,
+            ],
+        );
+
+        // making a pair beats adding to the existing one
+        let hand = Hand::parse_with_rules("2234J", &rules);
+        assert_eq!(hand.get_type(), TwoPair);
+        let cards = Hand::parse_with_jokers("22344").cards;
+        assert_eq!(rules.best_substitution(&hand), (cards, TwoPair));
+
+        // as does splitting the wild cards up
+        let hand = Hand::parse_with_rules("234JJ", &rules);
+        assert_eq!(hand.get_type(), TwoPair);
+        let cards = Hand::parse_with_jokers("23443").cards;
+        assert_eq!(rules.best_substitution(&hand), (cards, TwoPair));
+
+        // still a full house from two pair and a wild card
+        assert_eq!(
+            Hand::parse_with_rules("2233J", &rules).get_type(),
+            FullHouse
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid card for ruleset: J")]
+    fn ruleset_rejects_unknown_card() {
+        let rules = Ruleset::new(
+            Card::Jack,
+            vec![Card::Two, Card::Three],
+            None,
+            HandType::ALL.to_vec(),
+        );
+        Hand::parse_with_rules("2323J", &rules);
+    }
+
+    #[test]
+    fn hand_sizes() {
+        for size in 1..=8 {
+            let rules = Ruleset::jokers().with_hand_size(size);
+            let wild = "J".repeat(size);
+            assert_eq!(
+                Hand::parse_with_rules(&wild, &rules).get_type(),
+                HandType::from_groups(&[size])
+            );
+        }
+    }
+
+    #[test]
+    fn hand_six_cards() {
+        let rules = Ruleset::standard().with_hand_size(6);
+        let cases = [
+            ("23456A", HandType::HighCard),
+            ("223456", HandType::OnePair),
+            ("223344", HandType::TwoPair),
+            ("222345", HandType::ThreeOfAKind),
+            ("222334", HandType::FullHouse),
+            ("222333", HandType::FullHouse),
+            ("222233", HandType::FourOfAKind),
+            ("222223", HandType::FiveOfAKind),
+            ("222222", HandType::SixOfAKind),
+        ];
+        for (hand, expected) in cases {
+            assert_eq!(
+                Hand::parse_with_rules(hand, &rules).get_type(),
+                expected,
+                "{hand}"
+            );
+        }
+
+        let rules = Ruleset::jokers().with_hand_size(6);
+        let hand = Hand::parse_with_rules("KTJJTK", &rules);
+        assert_eq!(hand.get_type(), HandType::FourOfAKind);
+
+        // ties are broken left to right across all six cards
+        let a = Hand::parse_with_rules("22345A", &Ruleset::standard().with_hand_size(6));
+        let b = Hand::parse_with_rules("22345K", &Ruleset::standard().with_hand_size(6));
+        assert!(a > b);
+    }
+
+    #[test]
+    fn card_table_six_cards() {
+        let rules = Ruleset::jokers().with_hand_size(6);
+        let table = "32T3K2 765\nT55J5T 684\nKK677K 28";
+        // two pair (32T3K2) < full house (KK677K) < four of a kind (T55J5T)
+        assert_eq!(
+            CardTable::parse_with_rules(table, &rules).winnings(),
+            765 + 28 * 2 + 684 * 3
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 5 cards, found 6")]
+    fn hand_wrong_size() {
+        Hand::parse("23456A");
+    }
+
+    #[test]
+    fn ruleset_best_substitution() {
+        let rules = Ruleset::jokers();
+        let cases = [
+            ("KTJJT", "KTTTT", HandType::FourOfAKind),
+            ("QJJQ2", "QQQQ2", HandType::FourOfAKind),
+            ("2J3J4", "24344", HandType::ThreeOfAKind),
+            ("JJJJJ", "AAAAA", HandType::FiveOfAKind),
+            ("23456", "23456", HandType::HighCard),
+        ];
+        for (hand, cards, expected) in cases {
+            let hand = Hand::parse_with_jokers(hand);
+            let cards = Hand::parse_with_jokers(cards).cards;
+            assert_eq!(rules.best_substitution(&hand), (cards, expected));
+        }
+
+        // without wild cards there's nothing to substitute
+        let hand = Hand::parse("KTJJT");
+        assert_eq!(
+            Ruleset::standard().best_substitution(&hand),
+            (hand.cards.clone(), HandType::TwoPair)
+        );
+    }
+
+    /// Classifies a hand by counting cards, with no wild cards.
+    fn plain_type(cards: &[Card]) -> HandType {
+        let mut counts = [0; 14];
+        for card in cards {
+            counts[*card as usize] += 1;
+        }
+        let mut groups: Vec<usize> = counts.into_iter().filter(|n| *n > 0).collect();
+        groups.sort_unstable_by(|a, b| b.cmp(a));
+        HandType::from_groups(&groups)
+    }
+
+    /// Tries every replacement for every wild card and returns the best type
+    /// any of them make.
+    fn brute_force_type(cards: &[Card], rules: &Ruleset) -> HandType {
+        let Some(wild) = rules.wild else {
+            return plain_type(cards);
+        };
+
+        let mut best = plain_type(cards);
+        let mut cards = cards.to_vec();
+        let wilds: Vec<usize> = (0..cards.len()).filter(|i| cards[*i] == wild).collect();
+        let replacements: Vec<Card> = rules.order.iter().copied().filter(|c| *c != wild).collect();
+        let mut choice = vec![0; wilds.len()];
+        loop {
+            for (i, r) in wilds.iter().zip(&choice) {
+                cards[*i] = replacements[*r];
+            }
+            best = best.max(plain_type(&cards));
+
+            let Some(next) = choice.iter().rposition(|r| r + 1 < replacements.len()) else {
+                return best;
+            };
+            choice[next] += 1;
+            choice[next + 1..].fill(0);
+        }
+    }
+
+    #[test]
+    fn ruleset_classify_matches_brute_force() {
+        let rules = Ruleset::jokers();
+        // the type only depends on which cards are in the hand, not their
+        // order, so each combination only needs brute forcing once
+        let mut brute_forced = HashMap::new();
+        for hand in HandStats::enumerate(&rules).hands {
+            let mut sorted = hand.cards.clone();
+            sorted.sort_unstable();
+            let expected = *brute_forced
+                .entry(sorted)
+                .or_insert_with_key(|cards| brute_force_type(cards, &rules));
+            assert_eq!(hand.get_type(), expected, "{hand:?}");
+
+            let (cards, type_) = rules.best_substitution(&hand);
+            assert_eq!(plain_type(&cards), type_, "{hand:?}");
+            assert!(!cards.contains(&Card::Joker));
+            for (before, after) in hand.cards.iter().zip(&cards) {
+                assert!(before == after || *before == Card::Joker);
+            }
+        }
+    }
+
+    #[test]
+    fn hand_stats_standard_distribution() {
+        let stats = HandStats::enumerate(&Ruleset::standard());
+        assert_eq!(stats.len(), 13usize.pow(5));
+
+        use HandType::*;
+        let expected = BTreeMap::from([
+            (HighCard, 154440),
+            (OnePair, 171600),
+            (TwoPair, 25740),
+            (ThreeOfAKind, 17160),
+            (FullHouse, 1560),
+            (FourOfAKind, 780),
+            (FiveOfAKind, 13),
+        ]);
+        assert_eq!(stats.type_distribution(), expected);
+
+        let n = stats.len() as f64;
+        assert_eq!(stats.percentile(&Hand::parse("23456")), 50.0 / n);
+        assert_eq!(stats.percentile(&Hand::parse("AAAAA")), 100.0 - 50.0 / n);
+    }
+
+    #[test]
+    fn hand_stats_jokers_distribution() {
+        let stats = HandStats::enumerate(&Ruleset::jokers());
+        let distribution = stats.type_distribution();
+        assert_eq!(distribution.values().sum::<usize>(), 13usize.pow(5));
+        // all jokers, or one other card plus any mix of jokers
+        assert_eq!(distribution[&HandType::FiveOfAKind], 1 + 12 * 31);
+        // a joker always makes at least a pair
+        assert_eq!(distribution[&HandType::HighCard], 12 * 11 * 10 * 9 * 8);
+    }
+
+    #[test]
+    fn hand_stats_expected_winnings() {
+        use Card::*;
+        let rules =
+            Ruleset::new(Jack, vec![Two, Three], None, HandType::ALL.to_vec()).with_hand_size(2);
+        // 23 < 32 < 22 < 33
+        let stats = HandStats::enumerate(&rules);
+        assert_eq!(stats.len(), 4);
+
+        let hand = Hand::parse_with_rules("22", &rules);
+        assert_eq!(stats.percentile(&hand), 62.5);
+        assert_eq!(stats.expected_winnings(&hand, 10, 0), 10.0);
+        assert_eq!(
+            stats.expected_winnings(&hand, 10, 3),
+            10.0 * (1.0 + 3.0 * 0.625)
+        );
+    }
+
+    #[test]
+    fn hand_stats_sample() {
+        let rules = Ruleset::jokers().with_hand_size(7);
+        let stats = HandStats::sample(&rules, 1000, 7);
+        assert_eq!(stats.len(), 1000);
+        assert_eq!(stats, HandStats::sample(&rules, 1000, 7));
+        assert_ne!(stats, HandStats::sample(&rules, 1000, 8));
+        assert_eq!(stats.type_distribution().values().sum::<usize>(), 1000);
+
+        let best = Hand::parse_with_rules("AAAAAAA", &rules);
+        assert!(stats.percentile(&best) > 99.0);
+    }
+
+    #[test]
+    fn hand_key() {
+        let hand = Hand::parse("32T3K");
+        assert_eq!(hand.key(), 0x1_1_0_8_1_b);
+
+        let hand = Hand::parse_with_jokers("KTJJT");
+        assert_eq!(hand.key(), 0x5_b_9_0_0_9);
+    }
+
+    prop_compose! {
+        fn gen_hand()(labels in "[23456789TJQKA]{5}", jokers: bool) -> Hand {
+            if jokers {
+                Hand::parse_with_jokers(&labels)
+            } else {
+                Hand::parse(&labels)
+            }
+        }
+    }
+
+    prop_compose! {
+        fn gen_hand_bet()(hand in gen_hand(), bet in 0..10usize) -> HandBet {
+            HandBet { hand, bet: Bet(bet) }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn hand_cmp_agrees_with_partial_cmp(a in gen_hand(), b in gen_hand()) {
+            assert_eq!(a.partial_cmp(&b), Some(a.cmp(&b)));
+            assert_eq!(a.cmp(&b) == Ordering::Equal, a == b);
+        }
+
+        #[test]
+        fn hand_cmp_antisymmetric(a in gen_hand(), b in gen_hand()) {
+            assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+        }
+
+        #[test]
+        fn hand_cmp_transitive(a in gen_hand(), b in gen_hand(), c in gen_hand()) {
+            let mut hands = [a, b, c];
+            hands.sort();
+            let [a, b, c] = hands;
+            assert!(a <= b && b <= c);
+            assert!(a <= c);
+        }
+
+        #[test]
+        fn hand_cmp_type_first(a in gen_hand(), b in gen_hand()) {
+            if a.get_type() != b.get_type() {
+                assert_eq!(a.type_rank.cmp(&b.type_rank), a.cmp(&b));
+            }
+        }
+
+        #[test]
+        fn hand_bet_cmp_consistent(a in gen_hand_bet(), b in gen_hand_bet()) {
+            assert_eq!(a.partial_cmp(&b), Some(a.cmp(&b)));
+            assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+            assert_eq!(a.cmp(&b) == Ordering::Equal, a == b);
+        }
+        #[test]
+        fn hand_key_agrees_with_cmp(a in "[23456789TJQKA]{5}", b in "[23456789TJQKA]{5}", jokers: bool) {
+            let rules = if jokers { Ruleset::jokers() } else { Ruleset::standard() };
+            let a = Hand::parse_with_rules(&a, &rules);
+            let b = Hand::parse_with_rules(&b, &rules);
+            assert_eq!(a.key().cmp(&b.key()), a.cmp(&b));
+        }
+
+        #[test]
+        fn card_table_winnings_agree(lines in prop::collection::vec("[23456789TJQKA]{5} [0-9]{1,3}", 0..50), jokers: bool) {
+            let rules = if jokers { Ruleset::jokers() } else { Ruleset::standard() };
+            let mut table = CardTable::parse_with_rules(&lines.join("\n"), &rules);
+            assert_eq!(table.winnings(), table.winnings_by_comparison());
+        }
+    }
 }
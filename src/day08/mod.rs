@@ -1,7 +1,12 @@
 #![allow(dead_code)]
 
 use crate::util;
-use std::{collections::BTreeMap, fmt};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::{self, Write},
+    sync::Arc,
+    thread,
+};
 
 pub const EXAMPLE: &str = include_str!("../../inputs/examples/day08.txt");
 pub const REAL: &str = include_str!("../../inputs/real/day08.txt");
@@ -15,29 +20,110 @@ pub fn part2(s: &str) -> usize {
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-struct Map {
+pub struct Map {
     instructions: Instructions,
     network: Network,
+    compiled: CompiledNetwork,
 }
 
 impl Map {
-    fn parse(s: &str) -> Self {
+    /// Parses a map and compiles its network for navigation.
+    pub fn parse(s: &str) -> Self {
+        Self::parse_with_rules(s, GhostRules::default())
+    }
+
+    /// Same as [`Map::parse`], with the given rules for where ghosts start
+    /// and end.
+    pub fn parse_with_rules(s: &str, ghosts: GhostRules) -> Self {
         let (instructions, network) = s
             .split_once("\n\n")
             .expect("invalid input: no double blank line");
 
+        Self::new(
+            Instructions::parse(instructions),
+            Network::parse_with_rules(network, ghosts),
+        )
+    }
+
+    fn new(instructions: Instructions, network: Network) -> Self {
         Self {
-            instructions: Instructions::parse(instructions),
-            network: Network::parse(network),
+            compiled: CompiledNetwork::compile(&network),
+            instructions,
+            network,
+        }
+    }
+
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+
+    /// Returns the nodes visited following the instructions from `start`,
+    /// up to and including the first node where `is_end` holds.
+    ///
+    /// Stops early if the walk gets into a loop that never reaches an end,
+    /// since it would go on forever.
+    pub fn path<F>(&self, start: Id, is_end: F) -> Vec<Id>
+    where
+        F: Fn(&Id) -> bool,
+    {
+        let mut path = vec![start.clone()];
+        let mut seen = BTreeSet::new();
+        let mut instructions = self.instructions.clone();
+        let mut current = start;
+        while !is_end(&current) && seen.insert((current.clone(), instructions.index)) {
+            let instruction = instructions.next().expect("no instructions");
+            let node = self
+                .network
+                .nodes
+                .get(&current)
+                .unwrap_or_else(|| panic!("no node: {current}"));
+            current = node.apply(instruction).clone();
+            path.push(current.clone());
         }
+        path
+    }
+
+    /// Returns the number of steps from `AAA` to `ZZZ`.
+    pub fn navigate(&self) -> usize {
+        self.compiled.apply_instructions(&self.instructions)
+    }
+
+    /// Returns the number of steps until every ghost is on an end node.
+    pub fn navigate_ghosts(&self) -> usize {
+        self.compiled.apply_ghost_instructions(&self.instructions)
+    }
+
+    /// Same as [`Map::navigate_ghosts`], but double checks answers of up to
+    /// `bound` steps by moving every ghost in lockstep, since the least
+    /// common multiple is only right when each ghost loops back to its end
+    /// node with the same period. Bigger answers are returned unchecked.
+    pub fn navigate_ghosts_checked(&self, bound: usize) -> Result<usize, String> {
+        let steps = self.navigate_ghosts();
+        if steps <= bound {
+            self.compiled.replay_ghosts(&self.instructions, steps)?;
+        }
+        Ok(steps)
+    }
+
+    /// Same as [`Map::navigate`], looking nodes up by id at every step.
+    pub fn navigate_by_id(&self) -> usize {
+        self.network.apply_instructions(self.instructions.clone())
     }
 
-    fn navigate(self) -> usize {
-        self.network.apply_instructions(self.instructions)
+    /// Same as [`Map::navigate_ghosts`], looking nodes up by id at every
+    /// step.
+    pub fn navigate_ghosts_by_id(&self) -> usize {
+        self.network
+            .apply_ghost_instructions(self.instructions.clone())
     }
 
-    fn navigate_ghosts(self) -> usize {
-        self.network.apply_ghost_instructions(self.instructions)
+    /// Builds a table for jumping over whole passes through the
+    /// instructions.
+    ///
+    /// The table covers every node, so this panics if any node leads to an
+    /// id that isn't defined, even one no ghost would reach.
+    pub fn pass_table(&self) -> PassTable<'_> {
+        PassTable::build(&self.compiled, &self.instructions)
     }
 }
 
@@ -105,75 +191,185 @@ impl Instruction {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
-struct Id(char, char, char);
+/// Names a node. Any length works; names are shared between every node that
+/// mentions them, so cloning an id is cheap.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+pub struct Id(Arc<str>);
 
 impl Id {
+    const START: &'static str = "AAA";
+    const END: &'static str = "ZZZ";
+
+    fn new(name: &str) -> Self {
+        Self(name.into())
+    }
+
     fn start_node() -> Self {
-        Self('A', 'A', 'A')
+        Self::new(Self::START)
     }
 
     fn end_node() -> Self {
-        Self('Z', 'Z', 'Z')
+        Self::new(Self::END)
+    }
+
+    // compare names rather than building an id to compare against, which
+    // would allocate on every call
+    fn is_start(&self) -> bool {
+        self.as_str() == Self::START
     }
 
     fn is_end(&self) -> bool {
-        *self == Self::end_node()
+        self.as_str() == Self::END
     }
 
-    fn is_ghost_end(&self) -> bool {
-        self.2 == 'Z'
+    pub fn parse(s: &str) -> Self {
+        Interner::default().intern(s)
     }
 
-    fn is_ghost_start(&self) -> bool {
-        self.2 == 'A'
+    fn as_str(&self) -> &str {
+        &self.0
     }
 
-    fn parse(s: &str) -> Self {
-        let mut chars = s.trim().chars();
-        let a = chars.next().unwrap();
-        let b = chars.next().unwrap();
-        let c = chars.next().unwrap();
-        assert!(chars.next().is_none(), "expected end of string");
-        Self(a, b, c)
+    fn dot(&self) -> String {
+        format!("\"{}\"", util::dot_escape(self.as_str()))
+    }
+
+    fn json(&self) -> String {
+        format!("\"{}\"", util::json_escape(self.as_str()))
     }
 }
 
 impl fmt::Display for Id {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let Self(a, b, c) = self;
-        write!(f, "{}{}{}", a, b, c)
+        f.write_str(self.as_str())
+    }
+}
+
+/// Hands out one shared [`Id`] per distinct name.
+#[derive(Debug, Default)]
+struct Interner(HashSet<Arc<str>>);
+
+impl Interner {
+    fn intern(&mut self, s: &str) -> Id {
+        let name = s.trim();
+        assert!(!name.is_empty(), "expected an id");
+        assert!(
+            !name.contains(char::is_whitespace),
+            "unexpected whitespace in id: {name}"
+        );
+
+        if let Some(name) = self.0.get(name) {
+            return Id(name.clone());
+        }
+        let name: Arc<str> = name.into();
+        self.0.insert(name.clone());
+        Id(name)
+    }
+}
+
+/// Matches ids, used to pick out the nodes ghosts start and end on.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+pub enum IdPattern {
+    /// ids starting with the given text
+    Prefix(String),
+
+    /// ids ending with the given text
+    Suffix(String),
+
+    /// ids matching a glob, where `?` matches any one character and `*`
+    /// matches any run of characters, e.g. `?1*Z`
+    Glob(String),
+}
+
+impl IdPattern {
+    pub fn matches(&self, id: &Id) -> bool {
+        match self {
+            Self::Prefix(prefix) => id.as_str().starts_with(prefix.as_str()),
+            Self::Suffix(suffix) => id.as_str().ends_with(suffix.as_str()),
+            Self::Glob(glob) => {
+                let glob: Vec<char> = glob.chars().collect();
+                let name: Vec<char> = id.as_str().chars().collect();
+                glob_matches(&glob, &name)
+            }
+        }
+    }
+}
+
+fn glob_matches(glob: &[char], name: &[char]) -> bool {
+    match glob.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| glob_matches(rest, &name[i..])),
+        Some((c, rest)) => match name.split_first() {
+            Some((n, name)) if *c == '?' || c == n => glob_matches(rest, name),
+            _ => false,
+        },
+    }
+}
+
+/// Which nodes ghosts start and end on. By default they start on ids
+/// ending in `A` and end on ids ending in `Z`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+pub struct GhostRules {
+    start: IdPattern,
+    end: IdPattern,
+}
+
+impl Default for GhostRules {
+    fn default() -> Self {
+        Self::new(IdPattern::Suffix("A".into()), IdPattern::Suffix("Z".into()))
+    }
+}
+
+impl GhostRules {
+    pub fn new(start: IdPattern, end: IdPattern) -> Self {
+        Self { start, end }
+    }
+
+    pub fn is_start(&self, id: &Id) -> bool {
+        self.start.matches(id)
+    }
+
+    pub fn is_end(&self, id: &Id) -> bool {
+        self.end.matches(id)
     }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-struct Network(BTreeMap<Id, Node>);
+pub struct Network {
+    nodes: BTreeMap<Id, Node>,
+    ghosts: GhostRules,
+}
 
 impl Network {
-    fn parse(s: &str) -> Self {
+    pub fn parse(s: &str) -> Self {
+        Self::parse_with_rules(s, GhostRules::default())
+    }
+
+    pub fn parse_with_rules(s: &str, ghosts: GhostRules) -> Self {
+        let mut interner = Interner::default();
         let mut nodes = BTreeMap::new();
         for line in s.trim().lines() {
-            let node = Node::parse(line);
-            nodes.insert(node.id, node);
+            let node = Node::parse_interned(line, &mut interner);
+            nodes.insert(node.id.clone(), node);
         }
-        Self(nodes)
+        Self { nodes, ghosts }
     }
 
     fn ghost_start_ids(&self) -> impl Iterator<Item = &Id> {
-        self.0.keys().filter(|id| id.is_ghost_start())
+        self.nodes.keys().filter(|id| self.ghosts.is_start(id))
     }
 
     fn apply_instructions(&self, instructions: Instructions) -> usize {
         let mut step = 0;
-        let mut current = self.0.get(&Id::start_node()).expect("no start node");
+        let mut current = self.nodes.get(&Id::start_node()).expect("no start node");
         for instruction in instructions {
             step += 1;
             let next_id = current.apply(instruction);
 
             current = self
-                .0
-                .get(&next_id)
-                .expect(&format!("no next node: {next_id}"));
+                .nodes
+                .get(next_id)
+                .unwrap_or_else(|| panic!("no next node: {next_id}"));
 
             if current.is_end() {
                 break;
@@ -189,23 +385,25 @@ impl Network {
         // they'll all be on the end node at the same time
 
         self.ghost_start_ids()
-            .map(|id| self.0.get(id).expect(&format!("no start node: {id}")))
+            .map(|id| {
+                self.nodes
+                    .get(id)
+                    .unwrap_or_else(|| panic!("no start node: {id}"))
+            })
             .map(|node| {
                 let mut step = 0;
                 let mut current = node;
-                let map = &self.0;
+                let map = &self.nodes;
 
                 for instruction in instructions.clone() {
                     step += 1;
                     let next_id = current.apply(instruction);
 
                     current = map
-                        .get(&next_id)
-                        .expect(&format!("no next node: {next_id}"));
+                        .get(next_id)
+                        .unwrap_or_else(|| panic!("no next node: {next_id}"));
 
-                    if current.is_ghost_end() {
-                        let end_id = current.id;
-                        println!("found end at step {end_id}, {step} steps");
+                    if self.ghosts.is_end(&current.id) {
                         break;
                     }
                 }
@@ -213,6 +411,412 @@ impl Network {
             })
             .fold(1, util::least_common_multiple)
     }
+
+    /// Classes for graph exports, e.g. `ghost-start` for `11A`.
+    fn classes(&self, id: &Id) -> Vec<&'static str> {
+        let mut classes = vec![];
+        if id.is_start() {
+            classes.push("start");
+        }
+        if id.is_end() {
+            classes.push("end");
+        }
+        if self.ghosts.is_start(id) {
+            classes.push("ghost-start");
+        }
+        if self.ghosts.is_end(id) {
+            classes.push("ghost-end");
+        }
+        classes
+    }
+
+    /// Renders the network in Graphviz DOT format, with an edge per
+    /// direction labelled `L` or `R`.
+    ///
+    /// The start node is filled green and the end node red. Ghost start and
+    /// end nodes get a double border and a `ghost-start` or `ghost-end`
+    /// class, which ends up in SVG output. Steps along `path`, if any, are
+    /// drawn in bold blue.
+    pub fn to_dot(&self, path: &[Id]) -> String {
+        let on_path: BTreeSet<&Id> = path.iter().collect();
+        let steps: BTreeSet<(&Id, &Id)> = path.windows(2).map(|w| (&w[0], &w[1])).collect();
+
+        let mut out = String::from("digraph network {\n");
+        for id in self.nodes.keys() {
+            let mut attrs = vec![format!("class=\"{}\"", self.classes(id).join(" "))];
+            if id.is_start() {
+                attrs.push("style=filled, fillcolor=palegreen".to_string());
+            } else if id.is_end() {
+                attrs.push("style=filled, fillcolor=salmon".to_string());
+            }
+            if self.ghosts.is_start(id) || self.ghosts.is_end(id) {
+                attrs.push("peripheries=2".to_string());
+            }
+            if on_path.contains(id) {
+                attrs.push("color=blue, penwidth=2".to_string());
+            }
+            writeln!(out, "  {} [{}];", id.dot(), attrs.join(", ")).unwrap();
+        }
+
+        for node in self.nodes.values() {
+            for (label, to) in [("L", &node.left), ("R", &node.right)] {
+                let style = if steps.contains(&(&node.id, to)) {
+                    ", color=blue, penwidth=2"
+                } else {
+                    ""
+                };
+                let (from, to) = (node.id.dot(), to.dot());
+                writeln!(out, "  {from} -> {to} [label=\"{label}\"{style}];").unwrap();
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders the network as JSON: a `nodes` list, each with its `left`
+    /// and `right` neighbours and its classes (`start`, `end`,
+    /// `ghost-start`, `ghost-end`), and the `path` as a list of ids.
+    pub fn to_json(&self, path: &[Id]) -> String {
+        let nodes: Vec<String> = self
+            .nodes
+            .values()
+            .map(|node| {
+                let classes: Vec<String> = self
+                    .classes(&node.id)
+                    .iter()
+                    .map(|c| format!("\"{c}\""))
+                    .collect();
+                format!(
+                    "{{\"id\":{},\"left\":{},\"right\":{},\"classes\":[{}]}}",
+                    node.id.json(),
+                    node.left.json(),
+                    node.right.json(),
+                    classes.join(",")
+                )
+            })
+            .collect();
+        let path: Vec<String> = path.iter().map(Id::json).collect();
+
+        format!(
+            "{{\"nodes\":[{}],\"path\":[{}]}}",
+            nodes.join(","),
+            path.join(",")
+        )
+    }
+}
+
+/// Position of a node in a [`CompiledNetwork`].
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+struct NodeIndex(u32);
+
+impl NodeIndex {
+    fn new(index: usize) -> Self {
+        Self(u32::try_from(index).expect("too many nodes"))
+    }
+
+    fn get(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A [`Network`] compiled for navigation.
+///
+/// Ids are interned into dense indices once, up front, so each step is an
+/// index into a `Vec` rather than a map lookup keyed by id.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CompiledNetwork {
+    /// id of each node, by index
+    ids: Vec<Id>,
+
+    /// index of each id
+    indices: BTreeMap<Id, NodeIndex>,
+
+    /// left and right neighbours of each node, by index; ids that are led
+    /// to but never defined come after every defined node and have none
+    links: Vec<[NodeIndex; 2]>,
+
+    /// nodes ghosts start on
+    ghost_starts: Vec<NodeIndex>,
+
+    /// whether each node is a ghost end, by index
+    ghost_ends: Vec<bool>,
+}
+
+impl CompiledNetwork {
+    /// Compiles a network. Ids that nodes lead to but that aren't in the
+    /// network still get an index, and only panic when a step tries to
+    /// leave one.
+    pub fn compile(network: &Network) -> Self {
+        let mut ids: Vec<Id> = network.nodes.keys().cloned().collect();
+        let undefined: BTreeSet<Id> = network
+            .nodes
+            .values()
+            .flat_map(|node| [&node.left, &node.right])
+            .filter(|id| !network.nodes.contains_key(*id))
+            .cloned()
+            .collect();
+        ids.extend(undefined);
+        let indices: BTreeMap<Id, NodeIndex> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id.clone(), NodeIndex::new(i)))
+            .collect();
+        let lookup = |id: &Id| indices[id];
+        let links = network
+            .nodes
+            .values()
+            .map(|node| [lookup(&node.left), lookup(&node.right)])
+            .collect();
+        let ghost_starts = network.ghost_start_ids().map(lookup).collect();
+        let ghost_ends = ids.iter().map(|id| network.ghosts.is_end(id)).collect();
+
+        Self {
+            ids,
+            indices,
+            links,
+            ghost_starts,
+            ghost_ends,
+        }
+    }
+
+    fn index_of(&self, id: &Id) -> Option<NodeIndex> {
+        self.indices.get(id).copied()
+    }
+
+    fn id(&self, index: NodeIndex) -> &Id {
+        &self.ids[index.get()]
+    }
+
+    fn ghost_starts(&self) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.ghost_starts.iter().copied()
+    }
+
+    fn is_ghost_end(&self, index: NodeIndex) -> bool {
+        self.ghost_ends[index.get()]
+    }
+
+    fn step(&self, from: NodeIndex, instruction: Instruction) -> NodeIndex {
+        let [left, right] = *self
+            .links
+            .get(from.get())
+            .unwrap_or_else(|| panic!("no node: {}", self.id(from)));
+        match instruction {
+            Instruction::Left => left,
+            Instruction::Right => right,
+        }
+    }
+
+    /// Returns how many steps it takes to get from `start` to a node where
+    /// `is_end` holds, always taking at least one step.
+    fn steps_until<F>(&self, start: NodeIndex, instructions: &Instructions, is_end: F) -> usize
+    where
+        F: Fn(NodeIndex) -> bool,
+    {
+        let mut current = start;
+        let mut steps = 0;
+        for instruction in instructions.clone() {
+            steps += 1;
+            current = self.step(current, instruction);
+            if is_end(current) {
+                break;
+            }
+        }
+        steps
+    }
+
+    fn apply_instructions(&self, instructions: &Instructions) -> usize {
+        let start = self.index_of(&Id::start_node()).expect("no start node");
+        let end = self.index_of(&Id::end_node()).expect("no end node");
+        self.steps_until(start, instructions, |i| i == end)
+    }
+
+    fn apply_ghost_instructions(&self, instructions: &Instructions) -> usize {
+        // same as `Network::apply_ghost_instructions`: each ghost loops
+        // back to its end node, so they all meet at the least common
+        // multiple of their step counts
+        self.ghost_steps(instructions)
+            .into_iter()
+            .fold(1, util::least_common_multiple)
+    }
+
+    /// Returns how many steps each ghost takes to reach an end, sharing
+    /// the ghosts out between at most one thread per available core.
+    fn ghost_steps(&self, instructions: &Instructions) -> Vec<usize> {
+        let starts: Vec<NodeIndex> = self.ghost_starts().collect();
+        if starts.is_empty() {
+            return Vec::new();
+        }
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk = starts.len().div_ceil(workers);
+        thread::scope(|scope| {
+            let handles: Vec<_> = starts
+                .chunks(chunk)
+                .map(|starts| {
+                    scope.spawn(move || {
+                        starts
+                            .iter()
+                            .map(|start| {
+                                self.steps_until(*start, instructions, |i| self.is_ghost_end(i))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("ghost thread panicked"))
+                .collect()
+        })
+    }
+
+    /// Moves every ghost together for `steps` steps, and checks they're all
+    /// on an end node at the last step and not all on one before that.
+    fn replay_ghosts(&self, instructions: &Instructions, steps: usize) -> Result<(), String> {
+        // cycle the raw instructions rather than the `Instructions`
+        // iterator, whose infinite loop guard would trip on long replays
+        let mut ghosts: Vec<NodeIndex> = self.ghost_starts().collect();
+        for (step, instruction) in (1..=steps).zip(instructions.data.iter().cycle()) {
+            for ghost in &mut ghosts {
+                *ghost = self.step(*ghost, *instruction);
+            }
+            if step < steps && ghosts.iter().all(|g| self.is_ghost_end(*g)) {
+                return Err(format!(
+                    "ghosts are all on end nodes at step {step}, before step {steps}"
+                ));
+            }
+        }
+
+        let stray: Vec<String> = ghosts
+            .iter()
+            .filter(|g| !self.is_ghost_end(**g))
+            .map(|g| self.id(*g).to_string())
+            .collect();
+        if stray.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "ghosts not on end nodes at step {steps}: {}",
+                stray.join(", ")
+            ))
+        }
+    }
+}
+
+/// Jumps over whole passes through the instructions, so positions billions
+/// of steps away can be found without walking there.
+///
+/// Level `k` of the table says where each node ends up after `2^k` passes,
+/// and whether a ghost end was hit on the way. Any number of passes is then
+/// at most one jump per bit (binary lifting).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PassTable<'a> {
+    network: &'a CompiledNetwork,
+    instructions: Vec<Instruction>,
+
+    /// steps into a single pass at which a ghost end is reached, by node
+    end_hits: Vec<Vec<usize>>,
+
+    /// node reached after `2^k` passes, by level `k` then node
+    jumps: Vec<Vec<NodeIndex>>,
+
+    /// whether a ghost end is reached within `2^k` passes, by level `k`
+    /// then node
+    hits: Vec<Vec<bool>>,
+}
+
+impl<'a> PassTable<'a> {
+    /// Enough levels for a trillion passes.
+    const LEVELS: usize = 40;
+
+    fn build(network: &'a CompiledNetwork, instructions: &Instructions) -> Self {
+        let instructions = instructions.data.clone();
+        assert!(!instructions.is_empty(), "no instructions");
+
+        let mut end_hits = vec![];
+        let mut landings = vec![];
+        for start in 0..network.ids.len() {
+            let mut current = NodeIndex::new(start);
+            let mut ends = vec![];
+            for (i, instruction) in instructions.iter().enumerate() {
+                current = network.step(current, *instruction);
+                if network.is_ghost_end(current) {
+                    ends.push(i + 1);
+                }
+            }
+            end_hits.push(ends);
+            landings.push(current);
+        }
+
+        let mut jumps = vec![landings];
+        let mut hits = vec![end_hits.iter().map(|e| !e.is_empty()).collect::<Vec<_>>()];
+        for level in 1..Self::LEVELS {
+            let (prev_jumps, prev_hits) = (&jumps[level - 1], &hits[level - 1]);
+            let next_jumps = prev_jumps.iter().map(|j| prev_jumps[j.get()]).collect();
+            let next_hits = (0..prev_jumps.len())
+                .map(|i| prev_hits[i] || prev_hits[prev_jumps[i].get()])
+                .collect();
+            jumps.push(next_jumps);
+            hits.push(next_hits);
+        }
+
+        Self {
+            network,
+            instructions,
+            end_hits,
+            jumps,
+            hits,
+        }
+    }
+
+    fn index_of(&self, id: &Id) -> NodeIndex {
+        self.network
+            .index_of(id)
+            .unwrap_or_else(|| panic!("no node: {id}"))
+    }
+
+    /// Returns the steps into a single pass, starting from `start`, at
+    /// which a ghost end is reached.
+    pub fn end_hits(&self, start: &Id) -> &[usize] {
+        &self.end_hits[self.index_of(start).get()]
+    }
+
+    /// Returns where a ghost starting from `start` is after `steps` steps.
+    pub fn position_after(&self, start: &Id, steps: u64) -> &Id {
+        let len = self.instructions.len() as u64;
+        let (passes, rest) = (steps / len, steps % len);
+        assert!(passes >> Self::LEVELS == 0, "too many steps: {steps}");
+
+        let mut current = self.index_of(start);
+        for (level, jumps) in self.jumps.iter().enumerate() {
+            if passes >> level & 1 == 1 {
+                current = jumps[current.get()];
+            }
+        }
+        for instruction in &self.instructions[..rest as usize] {
+            current = self.network.step(current, *instruction);
+        }
+        self.network.id(current)
+    }
+
+    /// Returns how many steps it takes a ghost starting from `start` to
+    /// first reach a ghost end, or `None` if it doesn't within a trillion
+    /// passes.
+    pub fn first_end(&self, start: &Id) -> Option<u64> {
+        // skip the biggest run of passes that doesn't hit an end, then the
+        // next pass must hit one
+        let mut current = self.index_of(start);
+        let mut passes: u64 = 0;
+        for level in (0..Self::LEVELS).rev() {
+            if !self.hits[level][current.get()] {
+                current = self.jumps[level][current.get()];
+                passes += 1 << level;
+            }
+        }
+
+        let step = *self.end_hits[current.get()].first()?;
+        Some(passes * self.instructions.len() as u64 + step as u64)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -227,36 +831,36 @@ impl Node {
         self.id.is_end()
     }
 
-    fn is_ghost_end(&self) -> bool {
-        self.id.is_ghost_end()
-    }
-
-    fn apply(&self, instruction: Instruction) -> Id {
+    fn apply(&self, instruction: Instruction) -> &Id {
         match instruction {
-            Instruction::Left => self.left,
-            Instruction::Right => self.right,
+            Instruction::Left => &self.left,
+            Instruction::Right => &self.right,
         }
     }
 
     fn parse(s: &str) -> Self {
+        Self::parse_interned(s, &mut Interner::default())
+    }
+
+    fn parse_interned(s: &str, interner: &mut Interner) -> Self {
         let (id, pair) = s
             .trim()
             .split_once("=")
-            .expect(&format!("expected `=` (s: {s}"));
+            .unwrap_or_else(|| panic!("expected `=` (s: {s}"));
         let pair = pair
             .trim()
             .strip_prefix('(')
-            .expect(&format!("expected `(` (s: {s}"))
+            .unwrap_or_else(|| panic!("expected `(` (s: {s}"))
             .strip_suffix(')')
-            .expect(&format!("expected `)` (s: {s}"));
+            .unwrap_or_else(|| panic!("expected `)` (s: {s}"));
         let (left, right) = pair
             .trim()
             .split_once(",")
-            .expect(&format!("expected `,` (s: {s}"));
+            .unwrap_or_else(|| panic!("expected `,` (s: {s}"));
 
-        let id = Id::parse(id);
-        let left = Id::parse(left);
-        let right = Id::parse(right);
+        let id = interner.intern(id);
+        let left = interner.intern(left);
+        let right = interner.intern(right);
         Self { id, left, right }
     }
 }
@@ -266,21 +870,22 @@ mod tests {
     use super::*;
     use proptest::prelude::*;
 
+    const EXAMPLE_GHOSTS: &str = "
+        LR
+
+        11A = (11B, XXX)
+        11B = (XXX, 11Z)
+        11Z = (11B, XXX)
+        22A = (22B, XXX)
+        22B = (22C, 22C)
+        22C = (22Z, 22Z)
+        22Z = (22B, 22B)
+        XXX = (XXX, XXX)
+    ";
+
     #[test]
     fn network_apply_ghost_instructions() {
-        let map = "
-            LR
-
-            11A = (11B, XXX)
-            11B = (XXX, 11Z)
-            11Z = (11B, XXX)
-            22A = (22B, XXX)
-            22B = (22C, 22C)
-            22C = (22Z, 22Z)
-            22Z = (22B, 22B)
-            XXX = (XXX, XXX)
-        ";
-        let map = Map::parse(map);
+        let map = Map::parse(EXAMPLE_GHOSTS);
         let result = map.navigate_ghosts();
         assert_eq!(result, 6);
     }
@@ -306,35 +911,339 @@ mod tests {
 
     proptest! {
         #[test]
-        fn id_is_ghost_start_ends_in_a(a: char, b: char) {
-            let id = Id(a, b, 'A');
-            assert_eq!(id.is_ghost_start(), true);
+        fn ghost_rules_start_ends_in_a(name in "\\PC*") {
+            let id = Id::new(&format!("{name}A"));
+            assert!(GhostRules::default().is_start(&id));
+
+        }
+        #[test]
+        fn ghost_rules_end_ends_in_z(name in "\\PC*") {
+            let id = Id::new(&format!("{name}Z"));
+            assert!(GhostRules::default().is_end(&id));
+
+        }
+    }
+
+    #[test]
+    fn ghost_rules_is_start() {
+        let rules = GhostRules::default();
+        assert!(rules.is_start(&Id::new("ZZA")));
+        assert!(!rules.is_start(&Id::new("ZAZ")));
+    }
+
+    #[test]
+    fn ghost_rules_is_end() {
+        let rules = GhostRules::default();
+        assert!(rules.is_end(&Id::new("JJZ")));
+        assert!(!rules.is_end(&Id::new("ZZA")));
+    }
+
+    #[test]
+    fn id_pattern_matches() {
+        let id = Id::new("START1");
+        assert!(IdPattern::Prefix("ST".into()).matches(&id));
+        assert!(!IdPattern::Prefix("T".into()).matches(&id));
+        assert!(IdPattern::Suffix("T1".into()).matches(&id));
+        assert!(!IdPattern::Suffix("T".into()).matches(&id));
+
+        for glob in ["START1", "S*", "*1", "S?A*?", "*", "*T*T*"] {
+            assert!(IdPattern::Glob(glob.into()).matches(&id), "{glob}");
+        }
+        for glob in ["START", "?", "S?", "*2", "*TT*"] {
+            assert!(!IdPattern::Glob(glob.into()).matches(&id), "{glob}");
+        }
+    }
+
+    #[test]
+    fn map_long_ids() {
+        let input = "
+            LR
+
+            START1 = (MID1, MID1)
+            MID1 = (EXIT1, EXIT1)
+            EXIT1 = (MID1, MID1)
+            START22 = (FIRST22, FIRST22)
+            FIRST22 = (SECOND22, SECOND22)
+            SECOND22 = (EXIT22, EXIT22)
+            EXIT22 = (FIRST22, FIRST22)
+        ";
+        let rules = GhostRules::new(
+            IdPattern::Prefix("START".into()),
+            IdPattern::Glob("EXIT*".into()),
+        );
+        let map = Map::parse_with_rules(input, rules);
+        // one ghost ends every 2 steps, the other every 3
+        assert_eq!(map.navigate_ghosts(), 6);
+        assert_eq!(map.navigate_ghosts(), map.navigate_ghosts_by_id());
+
+        let network = map.network();
+        assert_eq!(
+            network.ghost_start_ids().collect::<Vec<_>>(),
+            vec![&Id::parse("START1"), &Id::parse("START22")]
+        );
+        assert_eq!(network.classes(&Id::parse("EXIT22")), ["ghost-end"]);
+    }
+
+    #[test]
+    fn network_parse_interns_ids() {
+        let network = Network::parse("AAA = (BBB, AAA)\nBBB = (AAA, BBB)");
+        let aaa = &network.nodes[&Id::parse("AAA")];
+        let bbb = &network.nodes[&Id::parse("BBB")];
+        assert!(Arc::ptr_eq(&aaa.id.0, &bbb.left.0));
+        assert!(Arc::ptr_eq(&aaa.right.0, &bbb.left.0));
+    }
+
+    #[test]
+    fn compiled_network_matches_network() {
+        let map = Map::parse(REAL);
+        assert_eq!(map.navigate(), map.navigate_by_id());
+        assert_eq!(map.navigate_ghosts(), map.navigate_ghosts_by_id());
+    }
+
+    #[test]
+    fn compiled_network_compile() {
+        let network = Network::parse("AAA = (BBB, ZZZ)\nBBB = (ZZZ, AAA)\nZZZ = (ZZZ, ZZZ)");
+        let compiled = CompiledNetwork::compile(&network);
+        let index = |id| compiled.index_of(&Id::parse(id)).unwrap();
+
+        assert_eq!(
+            compiled.links[index("AAA").get()],
+            [index("BBB"), index("ZZZ")]
+        );
+        assert_eq!(
+            compiled.step(index("BBB"), Instruction::Right),
+            index("AAA")
+        );
+        assert_eq!(compiled.id(index("ZZZ")), &Id::parse("ZZZ"));
+        assert_eq!(compiled.index_of(&Id::parse("XXX")), None);
+        assert!(compiled.is_ghost_end(index("ZZZ")));
+        assert_eq!(compiled.ghost_starts().collect::<Vec<_>>(), [index("AAA")]);
+    }
+
+    #[test]
+    fn compiled_network_missing_node() {
+        let compiled = CompiledNetwork::compile(&Network::parse("AAA = (XXX, AAA)"));
+        let xxx = compiled.index_of(&Id::parse("XXX")).unwrap();
+        assert_eq!(
+            compiled.step(
+                compiled.index_of(&Id::start_node()).unwrap(),
+                Instruction::Left
+            ),
+            xxx
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "no node: XXX")]
+    fn compiled_network_step_from_missing_node() {
+        let compiled = CompiledNetwork::compile(&Network::parse("AAA = (XXX, AAA)"));
+        compiled.step(
+            compiled.index_of(&Id::parse("XXX")).unwrap(),
+            Instruction::Left,
+        );
+    }
+
+    /// Walks `steps` steps from `start` one at a time.
+    fn walk(map: &Map, start: &Id, steps: usize) -> Id {
+        let compiled = &map.compiled;
+        let mut current = compiled.index_of(start).unwrap();
+        for instruction in map.instructions.clone().take(steps) {
+            current = compiled.step(current, instruction);
+        }
+        compiled.id(current).clone()
+    }
+
+    #[test]
+    fn pass_table_first_end() {
+        let map = Map::parse(REAL);
+        let table = map.pass_table();
+        for start in map.network.ghost_start_ids() {
+            let index = map.compiled.index_of(start).unwrap();
+            let expected = map
+                .compiled
+                .steps_until(index, &map.instructions, |i| map.compiled.is_ghost_end(i));
+            assert_eq!(table.first_end(start), Some(expected as u64), "{start}");
+        }
+
+        let map = Map::parse("LR\n\n11A = (11B, 11B)\n11B = (11A, 11A)");
+        assert_eq!(map.pass_table().first_end(&Id::parse("11A")), None);
+    }
 
+    #[test]
+    fn pass_table_end_hits() {
+        let map = Map::parse(
+            "LR\n\n11A = (11B, XXX)\n11B = (XXX, 11Z)\n11Z = (11B, XXX)\nXXX = (XXX, XXX)",
+        );
+        let table = map.pass_table();
+        assert_eq!(table.end_hits(&Id::parse("11A")), [2]);
+        assert_eq!(table.end_hits(&Id::parse("11Z")), [2]);
+        // passes always start with the first instruction, `L` here
+        assert_eq!(table.end_hits(&Id::parse("11B")), [] as [usize; 0]);
+        assert_eq!(
+            table.position_after(&Id::parse("11A"), 2),
+            &Id::parse("11Z")
+        );
+    }
+
+    #[test]
+    fn pass_table_trillions_of_steps() {
+        let map = Map::parse(REAL);
+        let table = map.pass_table();
+        let answer = map.navigate_ghosts() as u64;
+        assert!(answer > 1_000_000_000_000);
+        for start in map.network.ghost_start_ids() {
+            let id = table.position_after(start, answer);
+            assert!(map.network.ghosts.is_end(id), "{start} is on {id}");
         }
+    }
+
+    proptest! {
         #[test]
-        fn id_is_ghost_end_ends_in_z(a: char, b: char) {
-            let id = Id(a, b, 'Z');
-            assert_eq!(id.is_ghost_end(), true);
+        fn pass_table_position_after_matches_walking(steps in 0..20_000usize) {
+            let map = Map::parse(REAL);
+            let table = map.pass_table();
+            for start in map.network.ghost_start_ids() {
+                assert_eq!(table.position_after(start, steps as u64), &walk(&map, start, steps));
+            }
+        }
+    }
+
+    #[test]
+    fn map_navigate_ghosts_checked() {
+        let map = Map::parse(EXAMPLE_GHOSTS);
+        assert_eq!(map.navigate_ghosts_checked(1000), Ok(6));
+        assert_eq!(map.navigate_ghosts_checked(0), Ok(6));
+        assert_eq!(map.navigate_ghosts_checked(2_000_000_000), Ok(6));
+
+        // the replay doesn't count towards the infinite loop guard, which
+        // would otherwise stop long replays
+        let instructions = Instructions {
+            steps_taken: 1_000_000_000,
+            ..map.instructions.clone()
+        };
+        assert_eq!(map.compiled.replay_ghosts(&instructions, 6), Ok(()));
+
+        // 11A reaches its end after 1 step but never comes back, so the
+        // least common multiple of 1 and 2 is wrong
+        let map = Map::parse(
+            "L\n\n11A = (11Z, 11Z)\n11Z = (11B, 11B)\n11B = (11B, 11B)\n\
+             22A = (22B, 22B)\n22B = (22Z, 22Z)\n22Z = (22B, 22B)",
+        );
+        assert_eq!(
+            map.navigate_ghosts_checked(1000),
+            Err("ghosts not on end nodes at step 2: 11B".to_string())
+        );
+        // too big to check
+        assert_eq!(map.navigate_ghosts_checked(1), Ok(2));
+
+        // 11A stays on its end after 2 steps, so both ghosts first meet at
+        // step 3 rather than 6
+        let map = Map::parse(
+            "L\n\n11A = (11B, 11B)\n11B = (11Z, 11Z)\n11Z = (11Z, 11Z)\n\
+             22A = (22B, 22B)\n22B = (22C, 22C)\n22C = (22Z, 22Z)\n22Z = (22B, 22B)",
+        );
+        assert_eq!(
+            map.navigate_ghosts_checked(1000),
+            Err("ghosts are all on end nodes at step 3, before step 6".to_string())
+        );
+    }
 
+    #[test]
+    fn compiled_network_ghost_steps() {
+        let map = Map::parse(EXAMPLE_GHOSTS);
+        assert_eq!(map.compiled.ghost_steps(&map.instructions), [2, 3]);
+
+        let map = Map::parse(REAL);
+        let sequential: Vec<usize> = map
+            .compiled
+            .ghost_starts()
+            .map(|start| {
+                map.compiled
+                    .steps_until(start, &map.instructions, |i| map.compiled.is_ghost_end(i))
+            })
+            .collect();
+        assert_eq!(map.compiled.ghost_steps(&map.instructions), sequential);
+    }
+
+    #[test]
+    fn compiled_network_many_ghosts() {
+        // far more ghosts than cores, each one step further from the end
+        // than the next, so results have to come back in order
+        let mut input = String::from("L\n\n");
+        for i in 0..500 {
+            let next = if i == 499 {
+                "ENDZ".to_string()
+            } else {
+                format!("G{:03}A", i + 1)
+            };
+            input.push_str(&format!("G{i:03}A = ({next}, {next})\n"));
         }
+        input.push_str("ENDZ = (ENDZ, ENDZ)\n");
+
+        let map = Map::parse(&input);
+        let expected: Vec<usize> = (1..=500).rev().collect();
+        assert_eq!(map.compiled.ghost_steps(&map.instructions), expected);
     }
 
     #[test]
-    fn id_is_ghost_start() {
-        let id = Id('Z', 'Z', 'A');
-        assert_eq!(id.is_ghost_start(), true);
+    fn map_unreachable_missing_node() {
+        // BBB leads nowhere, but navigation never goes there
+        let map = Map::parse("L\n\nAAA = (ZZZ, BBB)\nBBB = (XXX, XXX)\nZZZ = (ZZZ, ZZZ)");
+        assert_eq!(map.navigate(), 1);
+        assert_eq!(map.navigate_by_id(), 1);
+    }
 
-        let id = Id('Z', 'A', 'Z');
-        assert_eq!(id.is_ghost_start(), false);
+    #[test]
+    #[should_panic(expected = "no node: XXX")]
+    fn map_reachable_missing_node() {
+        Map::parse("L\n\nAAA = (XXX, ZZZ)\nZZZ = (ZZZ, ZZZ)").navigate();
     }
 
     #[test]
-    fn id_is_ghost_end() {
-        let id = Id('J', 'J', 'Z');
-        assert_eq!(id.is_ghost_end(), true);
+    fn map_path() {
+        let map = Map::parse("LLR\n\nAAA = (BBB, BBB)\nBBB = (AAA, ZZZ)\nZZZ = (ZZZ, ZZZ)");
+        let path: Vec<String> = map
+            .path(Id::start_node(), Id::is_end)
+            .iter()
+            .map(Id::to_string)
+            .collect();
+        assert_eq!(path, ["AAA", "BBB", "AAA", "BBB", "AAA", "BBB", "ZZZ"]);
+
+        // never reaches the end, so stops once it starts repeating
+        let map = Map::parse("L\n\nAAA = (BBB, ZZZ)\nBBB = (AAA, ZZZ)\nZZZ = (ZZZ, ZZZ)");
+        assert_eq!(map.path(Id::start_node(), Id::is_end).len(), 3);
+    }
 
-        let id = Id('Z', 'Z', 'A');
-        assert_eq!(id.is_ghost_end(), false);
+    #[test]
+    fn network_to_dot() {
+        let map = Map::parse("LR\n\nAAA = (11Z, XXX)\n11Z = (11Z, 11Z)\nXXX = (XXX, XXX)");
+        let path = map.path(Id::start_node(), |id| GhostRules::default().is_end(id));
+        let expected = r#"digraph network {
+  "11Z" [class="ghost-end", peripheries=2, color=blue, penwidth=2];
+  "AAA" [class="start ghost-start", style=filled, fillcolor=palegreen, peripheries=2, color=blue, penwidth=2];
+  "XXX" [class=""];
+  "11Z" -> "11Z" [label="L"];
+  "11Z" -> "11Z" [label="R"];
+  "AAA" -> "11Z" [label="L", color=blue, penwidth=2];
+  "AAA" -> "XXX" [label="R"];
+  "XXX" -> "XXX" [label="L"];
+  "XXX" -> "XXX" [label="R"];
+}
+"#;
+        assert_eq!(map.network().to_dot(&path), expected);
+    }
+
+    #[test]
+    fn network_to_json() {
+        let network = Network::parse("AAA = (ZZZ, AAA)\nZZZ = (ZZZ, ZZZ)");
+        let path = [Id::parse("AAA"), Id::parse("ZZZ")];
+        let expected = concat!(
+            r#"{"nodes":["#,
+            r#"{"id":"AAA","left":"ZZZ","right":"AAA","classes":["start","ghost-start"]},"#,
+            r#"{"id":"ZZZ","left":"ZZZ","right":"ZZZ","classes":["end","ghost-end"]}"#,
+            r#"],"path":["AAA","ZZZ"]}"#
+        );
+        assert_eq!(network.to_json(&path), expected);
     }
 
     #[test]
@@ -364,16 +1273,16 @@ mod tests {
         ";
 
         let result = Map::parse(input);
-        let expect = Map {
-            instructions: Instructions::parse("LLR"),
-            network: Network::parse(
+        let expect = Map::new(
+            Instructions::parse("LLR"),
+            Network::parse(
                 "
                 AAA = (BBB, BBB)
                 BBB = (AAA, ZZZ)
                 ZZZ = (ZZZ, ZZZ)
             ",
             ),
-        };
+        );
 
         assert_eq!(result, expect);
     }
@@ -424,22 +1333,23 @@ mod tests {
             BBB = (AAA, ZZZ)
             ZZZ = (ZZZ, ZZZ)
         ";
-        let expect = Network(
-            vec![
+        let expect = Network {
+            nodes: vec![
                 (Id::parse("AAA"), Node::parse("AAA = (BBB, BBB)")),
                 (Id::parse("BBB"), Node::parse("BBB = (AAA, ZZZ)")),
                 (Id::parse("ZZZ"), Node::parse("ZZZ = (ZZZ, ZZZ)")),
             ]
             .into_iter()
             .collect(),
-        );
+            ghosts: GhostRules::default(),
+        };
         let result = Network::parse(input);
         assert_eq!(result, expect);
     }
 
     #[test]
     fn id_parse() {
-        let expect = Id('A', 'A', 'A');
+        let expect = Id::new("AAA");
         let result = Id::parse("AAA");
         assert_eq!(result, expect);
     }
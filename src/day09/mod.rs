@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 
+use std::{cmp::Ordering, sync::OnceLock};
+
 pub const EXAMPLE: &str = include_str!("../../inputs/examples/day09.txt");
 pub const REAL: &str = include_str!("../../inputs/real/day09.txt");
 
@@ -37,39 +39,134 @@ impl Report {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-struct History {
+#[derive(Debug)]
+pub struct History {
     data: Vec<isize>,
+
+    /// first value of each row of the difference table, from the data
+    /// itself down to the last row that isn't all zeros. Only built when
+    /// needed, and `None` if a difference doesn't fit in an `i128`.
+    differences: OnceLock<Option<Vec<i128>>>,
+}
+
+// the difference table is just a cache, so only the data counts
+impl PartialEq for History {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl Eq for History {}
+
+impl PartialOrd for History {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for History {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.data.cmp(&other.data)
+    }
 }
 
 impl History {
     fn new(data: Vec<isize>) -> Self {
-        Self { data }
+        Self {
+            data,
+            differences: OnceLock::new(),
+        }
+    }
+
+    fn differences(&self) -> Option<&[i128]> {
+        self.differences
+            .get_or_init(|| leading_differences(&self.data))
+            .as_deref()
+    }
+
+    /// Returns the degree of the polynomial the history follows, or `None`
+    /// if it's all zeros.
+    ///
+    /// A history of `n` values that never settles into a row of zeros is
+    /// treated as following the degree `n - 1` polynomial through every
+    /// value.
+    ///
+    /// Returns an error if the difference table doesn't fit in an `i128`,
+    /// which takes dozens of huge values.
+    pub fn degree(&self) -> Result<Option<usize>, String> {
+        let differences = self
+            .differences()
+            .ok_or_else(|| "difference table overflowed".to_string())?;
+        Ok(differences.len().checked_sub(1))
+    }
+
+    /// Returns the value at index `k` of the sequence, which can be past
+    /// either end: `k = len` is the next value and `k = -1` the one before
+    /// the first. Returns `None` if it, or any difference along the way,
+    /// doesn't fit.
+    ///
+    /// Uses Newton's forward difference formula,
+    /// `y(k) = sum of choose(k, j) * Δʲy(0)`, which works for negative `k`
+    /// too, so there's no need to extend the difference table step by step.
+    pub fn extrapolate(&self, k: isize) -> Option<isize> {
+        let k = k as i128;
+        let mut total: i128 = 0;
+        // choose(k, j), built up one factor at a time; each division is
+        // exact since the result is always a binomial coefficient
+        let mut choose: i128 = 1;
+        for (j, difference) in (0..).zip(self.differences()?) {
+            if j > 0 {
+                choose = choose.checked_mul(k - j + 1)? / j;
+            }
+            total = total.checked_add(choose.checked_mul(*difference)?)?;
+        }
+        isize::try_from(total).ok()
     }
 
     fn predict_next(&self) -> isize {
-        predict(self.data.clone(), vec![], Mode::Next).iter().sum()
+        predict(&self.data, Mode::Next)
     }
 
     fn predict_prior(&self) -> isize {
-        predict(self.data.clone(), vec![], Mode::Prior)
-            .into_iter()
-            .reduce(|a, b| b - a)
-            .expect("predict is empty")
+        predict(&self.data, Mode::Prior)
     }
 
     pub fn parse(input: &str) -> Self {
         let data = input
-            .trim()
             .split_whitespace()
             .map(|s| s.parse::<isize>().unwrap())
             .collect();
-        Self { data }
+        Self::new(data)
     }
 }
 
-fn has_converged(data: &Vec<isize>) -> bool {
-    !data.iter().any(|i| i != &0)
+/// Returns the first value of each row of the difference table, stopping
+/// before the first row of all zeros or after a row of one value. Returns
+/// `None` if a difference doesn't fit in an `i128`.
+fn leading_differences(data: &[isize]) -> Option<Vec<i128>> {
+    let mut leading = vec![];
+    let mut row: Vec<i128> = data.iter().map(|i| *i as i128).collect();
+    while row.iter().any(|i| *i != 0) {
+        leading.push(row[0]);
+        for i in 1..row.len() {
+            row[i - 1] = row[i].checked_sub(row[i - 1])?;
+        }
+        row.pop();
+    }
+    Some(leading)
+}
+
+/// Replaces a row of the difference table with the row below it, which is
+/// one value shorter.
+fn take_differences(row: &mut Vec<isize>) {
+    for i in 1..row.len() {
+        row[i - 1] = row[i] - row[i - 1];
+    }
+    row.pop();
+}
+
+fn has_converged(data: &[isize]) -> bool {
+    data.iter().all(|i| *i == 0)
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -81,8 +178,8 @@ enum Mode {
 impl Mode {
     fn is_next(&self) -> bool {
         match self {
-            Mode::Prior => true,
-            Mode::Next => false,
+            Mode::Prior => false,
+            Mode::Next => true,
         }
     }
 
@@ -91,25 +188,34 @@ impl Mode {
     }
 }
 
-fn predict(data: Vec<isize>, mut carry: Vec<isize>, mode: Mode) -> Vec<isize> {
-    match mode {
-        Mode::Prior => carry.push(*data.first().expect("empty series")),
-        Mode::Next => carry.push(*data.last().expect("empty series")),
-    }
-    if has_converged(&data) {
+/// Predicts the value just past one end of the series.
+///
+/// Works down the difference table one row at a time, reusing a single
+/// buffer, until a row is all zeros. The next value is the sum of the last
+/// value of every row. The prior value takes the first value of every row
+/// instead, with alternating signs: `a - (b - (c - ...))`.
+fn predict(data: &[isize], mode: Mode) -> isize {
+    assert!(!data.is_empty(), "empty series");
+
+    let mut row = data.to_vec();
+    let mut prediction = 0;
+    let mut sign = 1;
+    while !has_converged(&row) {
         if mode.is_next() {
-            carry.reverse();
+            prediction += row[row.len() - 1];
+        } else {
+            prediction += sign * row[0];
+            sign = -sign;
         }
-
-        return carry;
+        take_differences(&mut row);
     }
-    let next = data.windows(2).map(|x| x[1] - x[0]).collect();
-    predict(next, carry, mode)
+    prediction
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn history_predict_prior() {
@@ -163,4 +269,133 @@ mod tests {
         let result = History::parse(input);
         assert_eq!(result, expect);
     }
+
+    #[test]
+    fn history_degree() {
+        assert_eq!(History::parse("0 0 0").degree(), Ok(None));
+        assert_eq!(History::parse("7 7 7").degree(), Ok(Some(0)));
+        assert_eq!(History::parse("0 3 6 9 12 15").degree(), Ok(Some(1)));
+        assert_eq!(History::parse("1 3 6 10 15 21").degree(), Ok(Some(2)));
+        assert_eq!(
+            History::parse("10  13  16  21  30  45").degree(),
+            Ok(Some(3))
+        );
+        // never settles, so it's the cubic through all four points
+        assert_eq!(History::parse("1 0 0 0").degree(), Ok(Some(3)));
+    }
+
+    #[test]
+    fn history_extrapolate() {
+        let history = History::parse("10  13  16  21  30  45");
+        assert_eq!(history.extrapolate(6), Some(68));
+        assert_eq!(history.extrapolate(-1), Some(5));
+        for (k, value) in history.data.iter().enumerate() {
+            assert_eq!(history.extrapolate(k as isize), Some(*value));
+        }
+
+        // y = k^2
+        let history = History::parse("0 1 4 9");
+        assert_eq!(history.extrapolate(1_000_000), Some(1_000_000_000_000));
+        assert_eq!(history.extrapolate(-12), Some(144));
+
+        assert_eq!(History::parse("0 0").extrapolate(5), Some(0));
+        assert_eq!(History::parse("0 1 4 9").extrapolate(isize::MAX), None);
+    }
+
+    #[test]
+    fn mode_is_next() {
+        assert!(Mode::Next.is_next());
+        assert!(!Mode::Next.is_prior());
+        assert!(Mode::Prior.is_prior());
+        assert!(!Mode::Prior.is_next());
+    }
+
+    #[test]
+    fn predict_edge_cases() {
+        assert_eq!(predict(&[5], Mode::Next), 5);
+        assert_eq!(predict(&[5], Mode::Prior), 5);
+
+        // never reaches a row of zeros, so the last row is a single value
+        assert_eq!(predict(&[1, 0, 0, 0], Mode::Next), -1);
+        assert_eq!(predict(&[0, 0, 0, 1], Mode::Prior), -1);
+
+        let data: Vec<isize> = (0..100_000).collect();
+        assert_eq!(predict(&data, Mode::Next), 100_000);
+        assert_eq!(predict(&data, Mode::Prior), -1);
+    }
+
+    #[test]
+    fn history_extrapolate_overflow() {
+        let history = History::parse("-9223372036854775808 1");
+        assert_eq!(history.degree(), Ok(Some(1)));
+        assert_eq!(history.extrapolate(0), Some(isize::MIN));
+        assert_eq!(history.extrapolate(1), Some(1));
+        assert_eq!(history.extrapolate(2), None);
+
+        // the differences double with every row, until they overflow
+        let data = (0..80)
+            .map(|i| if i % 2 == 0 { isize::MIN } else { isize::MAX })
+            .collect();
+        let history = History::new(data);
+        assert_eq!(history.extrapolate(0), None);
+        assert_eq!(
+            history.degree(),
+            Err("difference table overflowed".to_string())
+        );
+    }
+
+    #[test]
+    fn history_differences_are_lazy() {
+        let history = History::parse("1 3 6 10 15 21");
+        assert_eq!(history.differences.get(), None);
+        assert_eq!(history.predict_next(), 28);
+        assert_eq!(history.differences.get(), None);
+        assert_eq!(history.degree(), Ok(Some(2)));
+        assert_eq!(history.differences.get(), Some(&Some(vec![1, 2, 1])));
+    }
+
+    /// Evaluates the polynomial with the given coefficients, lowest power
+    /// first.
+    fn evaluate(coefficients: &[isize], x: isize) -> isize {
+        coefficients.iter().rev().fold(0, |total, c| total * x + c)
+    }
+
+    prop_compose! {
+        /// A history following a random polynomial, along with the
+        /// polynomial's coefficients, lowest power first.
+        fn gen_polynomial_history()
+            (coefficients in prop::collection::vec(-5..=5isize, 1..6))
+            (len in coefficients.len() + 1..20, coefficients in Just(coefficients))
+            -> (History, Vec<isize>)
+        {
+            let data = (0..len as isize).map(|x| evaluate(&coefficients, x)).collect();
+            (History::new(data), coefficients)
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn history_extrapolate_matches_predict((history, _) in gen_polynomial_history()) {
+            let len = history.data.len() as isize;
+            assert_eq!(history.extrapolate(len), Some(history.predict_next()));
+            assert_eq!(history.extrapolate(-1), Some(history.predict_prior()));
+        }
+
+        #[test]
+        fn history_extrapolate_matches_polynomial(
+            (history, coefficients) in gen_polynomial_history(),
+            k in -50..50isize,
+        ) {
+            assert_eq!(history.extrapolate(k), Some(evaluate(&coefficients, k)));
+
+            let degree = coefficients.iter().rposition(|c| *c != 0);
+            assert_eq!(history.degree(), Ok(degree));
+        }
+        #[test]
+        fn predict_matches_polynomial((history, coefficients) in gen_polynomial_history()) {
+            let len = history.data.len() as isize;
+            assert_eq!(predict(&history.data, Mode::Next), evaluate(&coefficients, len));
+            assert_eq!(predict(&history.data, Mode::Prior), evaluate(&coefficients, -1));
+        }
+    }
 }
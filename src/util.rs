@@ -1,3 +1,5 @@
+use std::{fmt, ops};
+
 pub fn solve_quadratic(a: i32, b: i32, c: i32) -> (f64, f64) {
     let a = a as f64;
     let b = b as f64;
@@ -23,6 +25,27 @@ pub fn least_common_multiple(a: usize, b: usize) -> usize {
     (a * b) / greatest_common_factor(a, b)
 }
 
+/// Escapes a string for use inside a double-quoted Graphviz DOT string.
+pub fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escapes a string for use inside a double-quoted JSON string.
+pub fn json_escape(s: &str) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct Range {
     start: u32,
@@ -43,6 +66,16 @@ impl Range {
         Self { start, end }
     }
 
+    /// Returns the start of the range (inclusive).
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// Returns the end of the range (exclusive).
+    pub fn end(&self) -> u32 {
+        self.end
+    }
+
     /// Returns true if the given range overlaps with this range.
     ///
     /// # Examples
@@ -253,11 +286,322 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// A rectangular 2D grid of cells stored in row-major order.
+///
+/// Positions are `(row, col)` pairs with `(0, 0)` in the top left.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+impl Grid<char> {
+    /// Parses a grid of characters, one row per line.
+    ///
+    /// # Examples
+    /// ```
+    /// use aoc2023::util::Grid;
+    ///
+    /// let grid = Grid::parse("ab\ncd\n").unwrap();
+    /// assert_eq!(grid.width(), 2);
+    /// assert_eq!(grid.height(), 2);
+    /// assert_eq!(grid.get(1, 0), Some(&'c'));
+    ///
+    /// assert!(Grid::parse("ab\nc").is_err()); // rows must be the same width
+    /// ```
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::parse_with(s, |c| c)
+    }
+}
+
+impl<T> Grid<T> {
+    /// Creates a grid from row-major `cells`.
+    ///
+    /// Panics if `cells` doesn't contain exactly `width * height` items.
+    pub fn new(width: usize, height: usize, cells: Vec<T>) -> Self {
+        assert_eq!(cells.len(), width * height, "cells must fill the grid");
+        Self {
+            cells,
+            width,
+            height,
+        }
+    }
+
+    /// Parses a grid, converting each character with `f`. Every line must
+    /// have the same number of characters.
+    pub fn parse_with<F>(s: &str, mut f: F) -> Result<Self, String>
+    where
+        F: FnMut(char) -> T,
+    {
+        let mut cells = Vec::with_capacity(s.len());
+        let mut width = None;
+        let mut height = 0;
+
+        for (n, line) in s.lines().enumerate() {
+            let before = cells.len();
+            cells.extend(line.chars().map(&mut f));
+            let len = cells.len() - before;
+
+            match width {
+                None => width = Some(len),
+                Some(w) if w != len => {
+                    return Err(format!(
+                        "failed to parse grid: line {} has width {len}, expected {w}",
+                        n + 1
+                    ))
+                }
+                Some(_) => {}
+            }
+            height += 1;
+        }
+
+        Ok(Self::new(width.unwrap_or(0), height, cells))
+    }
+
+    /// Returns the number of columns.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the number of rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns true if the position is inside the grid.
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        row < self.height && col < self.width
+    }
+
+    /// Returns the cell at the given position, or `None` if it's out of
+    /// bounds.
+    pub fn get(&self, row: usize, col: usize) -> Option<&T> {
+        if !self.contains(row, col) {
+            return None;
+        }
+        self.cells.get(row * self.width + col)
+    }
+
+    /// Replaces the cell at the given position, returning the old value, or
+    /// `None` if the position is out of bounds.
+    pub fn set(&mut self, row: usize, col: usize, value: T) -> Option<T> {
+        if !self.contains(row, col) {
+            return None;
+        }
+        let cell = &mut self.cells[row * self.width + col];
+        Some(std::mem::replace(cell, value))
+    }
+
+    /// Returns the given row as a slice, or `None` if it's out of bounds.
+    pub fn row(&self, row: usize) -> Option<&[T]> {
+        if row >= self.height {
+            return None;
+        }
+        let start = row * self.width;
+        Some(&self.cells[start..start + self.width])
+    }
+
+    /// Returns an iterator over every row, top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.height).filter_map(|row| self.row(row))
+    }
+
+    /// Returns an iterator over the given column, top to bottom, or `None`
+    /// if it's out of bounds.
+    pub fn column(&self, col: usize) -> Option<impl Iterator<Item = &T>> {
+        if col >= self.width {
+            return None;
+        }
+        Some(self.cells.iter().skip(col).step_by(self.width))
+    }
+
+    /// Returns an iterator over every position and its cell in row-major
+    /// order.
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        let width = self.width;
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, cell)| ((i / width, i % width), cell))
+    }
+
+    /// Returns the positions of every cell matching the predicate in
+    /// row-major order.
+    ///
+    /// # Examples
+    /// ```
+    /// use aoc2023::util::Grid;
+    ///
+    /// let grid = Grid::parse("#.\n.#").unwrap();
+    /// let found: Vec<_> = grid.find_all(|c| *c == '#').collect();
+    /// assert_eq!(found, vec![(0, 0), (1, 1)]);
+    /// ```
+    pub fn find_all<'a, F>(&'a self, mut f: F) -> impl Iterator<Item = (usize, usize)> + 'a
+    where
+        F: FnMut(&T) -> bool + 'a,
+    {
+        self.iter()
+            .filter(move |(_, cell)| f(cell))
+            .map(|(pos, _)| pos)
+    }
+
+    /// Returns the in-bounds positions directly above, below, left and
+    /// right of the given position.
+    ///
+    /// # Examples
+    /// ```
+    /// use aoc2023::util::Grid;
+    ///
+    /// let grid = Grid::parse("abc\ndef\nghi").unwrap();
+    /// let corner: Vec<_> = grid.neighbours4(0, 0).collect();
+    /// assert_eq!(corner, vec![(1, 0), (0, 1)]);
+    ///
+    /// let center: Vec<_> = grid.neighbours4(1, 1).collect();
+    /// assert_eq!(center.len(), 4);
+    /// ```
+    pub fn neighbours4(&self, row: usize, col: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        const OFFSETS: [(isize, isize); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
+        self.offset_positions(row, col, &OFFSETS)
+    }
+
+    /// Returns the in-bounds positions surrounding the given position,
+    /// including diagonals.
+    ///
+    /// # Examples
+    /// ```
+    /// use aoc2023::util::Grid;
+    ///
+    /// let grid = Grid::parse("abc\ndef\nghi").unwrap();
+    /// assert_eq!(grid.neighbours8(0, 0).count(), 3);
+    /// assert_eq!(grid.neighbours8(1, 1).count(), 8);
+    /// assert_eq!(grid.neighbours8(2, 1).count(), 5);
+    /// ```
+    pub fn neighbours8(&self, row: usize, col: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        const OFFSETS: [(isize, isize); 8] = [
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+            (0, -1),
+            (0, 1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+        ];
+        self.offset_positions(row, col, &OFFSETS)
+    }
+
+    fn offset_positions(
+        &self,
+        row: usize,
+        col: usize,
+        offsets: &'static [(isize, isize)],
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        offsets.iter().filter_map(move |(dr, dc)| {
+            let row = row.checked_add_signed(*dr)?;
+            let col = col.checked_add_signed(*dc)?;
+            self.contains(row, col).then_some((row, col))
+        })
+    }
+
+    /// Returns a new grid with the given function applied to every cell.
+    pub fn map<U, F>(&self, f: F) -> Grid<U>
+    where
+        F: FnMut(&T) -> U,
+    {
+        Grid::new(self.width, self.height, self.cells.iter().map(f).collect())
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    /// Returns a new grid with rows and columns swapped.
+    ///
+    /// # Examples
+    /// ```
+    /// use aoc2023::util::Grid;
+    ///
+    /// let grid = Grid::parse("abc\ndef").unwrap();
+    /// assert_eq!(grid.transpose().to_string(), "ad\nbe\ncf");
+    /// ```
+    pub fn transpose(&self) -> Self {
+        let cells = (0..self.width)
+            .flat_map(|col| (0..self.height).map(move |row| (row, col)))
+            .map(|(row, col)| self[(row, col)].clone())
+            .collect();
+        Self::new(self.height, self.width, cells)
+    }
+
+    /// Returns a new grid rotated 90 degrees clockwise.
+    ///
+    /// # Examples
+    /// ```
+    /// use aoc2023::util::Grid;
+    ///
+    /// let grid = Grid::parse("abc\ndef").unwrap();
+    /// assert_eq!(grid.rotate_clockwise().to_string(), "da\neb\nfc");
+    /// ```
+    pub fn rotate_clockwise(&self) -> Self {
+        let mut rotated = self.transpose();
+        rotated.flip_rows();
+        rotated
+    }
+
+    /// Returns a new grid rotated 90 degrees counter-clockwise.
+    pub fn rotate_counter_clockwise(&self) -> Self {
+        let mut flipped = self.clone();
+        flipped.flip_rows();
+        flipped.transpose()
+    }
+
+    // reverse each row in place, i.e. mirror across the vertical axis
+    fn flip_rows(&mut self) {
+        if self.width == 0 {
+            return;
+        }
+        for row in self.cells.chunks_mut(self.width) {
+            row.reverse();
+        }
+    }
+}
+
+impl<T> ops::Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (row, col): (usize, usize)) -> &Self::Output {
+        self.get(row, col)
+            .unwrap_or_else(|| panic!("position out of bounds: ({row}, {col})"))
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, row) in self.rows().enumerate() {
+            if n > 0 {
+                writeln!(f)?;
+            }
+            for cell in row {
+                write!(f, "{cell}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use proptest::prelude::*;
 
+    #[test]
+    fn json_escape_special_chars() {
+        assert_eq!(json_escape("\"\\\n"), "\\\"\\\\\\u000a");
+    }
+
+    #[test]
+    fn dot_escape_special_chars() {
+        assert_eq!(dot_escape("a\"b\\c"), "a\\\"b\\\\c");
+    }
+
     #[test]
     fn test_greatest_common_factor() {
         assert_eq!(greatest_common_factor(12083, 20513), 281);
@@ -380,4 +724,109 @@ mod tests {
         assert!(a.is_adjacent(&b));
         assert!(b.is_adjacent(&a));
     }
+
+    prop_compose! {
+        fn gen_grid()(width in 1..8usize, height in 1..8usize)
+            (cells in prop::collection::vec(gen_ascii_char(), width * height), width in Just(width), height in Just(height))
+            -> Grid<char> {
+            Grid::new(width, height, cells)
+        }
+    }
+
+    proptest! { // Grid property tests
+        #[test]
+        fn grid_display_parse_round_trip(grid in gen_grid()) {
+            assert_eq!(Grid::parse(&grid.to_string()), Ok(grid));
+        }
+
+        #[test]
+        fn grid_transpose_twice_is_identity(grid in gen_grid()) {
+            assert_eq!(grid.transpose().transpose(), grid);
+        }
+
+        #[test]
+        fn grid_rotate_four_times_is_identity(grid in gen_grid()) {
+            let rotated = (0..4).fold(grid.clone(), |g, _| g.rotate_clockwise());
+            assert_eq!(rotated, grid);
+        }
+
+        #[test]
+        fn grid_rotate_inverse(grid in gen_grid()) {
+            assert_eq!(grid.rotate_clockwise().rotate_counter_clockwise(), grid);
+        }
+
+        #[test]
+        fn grid_neighbours_in_bounds(grid in gen_grid(), row in 0..8usize, col in 0..8usize) {
+            for (r, c) in grid.neighbours8(row, col) {
+                assert!(grid.contains(r, c));
+                assert!(r.abs_diff(row) <= 1 && c.abs_diff(col) <= 1);
+                assert!((r, c) != (row, col));
+            }
+        }
+    }
+
+    #[test]
+    fn test_grid_parse_ragged() {
+        assert!(Grid::parse("abc\nde\nfgh").is_err());
+    }
+
+    #[test]
+    fn test_grid_parse_empty() {
+        let grid = Grid::parse("").unwrap();
+        assert_eq!(grid.width(), 0);
+        assert_eq!(grid.height(), 0);
+        assert_eq!(grid.get(0, 0), None);
+        assert_eq!(grid.to_string(), "");
+    }
+
+    #[test]
+    fn test_grid_get_out_of_bounds() {
+        let grid = Grid::parse("ab\ncd").unwrap();
+        assert_eq!(grid.get(0, 1), Some(&'b'));
+        assert_eq!(grid.get(0, 2), None);
+        assert_eq!(grid.get(2, 0), None);
+        assert_eq!(grid.get(usize::MAX, usize::MAX), None);
+    }
+
+    #[test]
+    fn test_grid_rows_and_columns() {
+        let grid = Grid::parse("abc\ndef").unwrap();
+        assert_eq!(grid.row(1), Some(&['d', 'e', 'f'][..]));
+        assert_eq!(grid.row(2), None);
+        assert_eq!(
+            grid.column(2).map(|c| c.collect::<String>()),
+            Some("cf".to_string())
+        );
+        assert!(grid.column(3).is_none());
+        assert_eq!(grid.rows().count(), 2);
+    }
+
+    #[test]
+    fn test_grid_neighbours_edges() {
+        let grid = Grid::parse("abc\ndef\nghi").unwrap();
+
+        let mut bottom_right: Vec<_> = grid.neighbours8(2, 2).collect();
+        bottom_right.sort();
+        assert_eq!(bottom_right, vec![(1, 1), (1, 2), (2, 1)]);
+
+        let mut top_edge: Vec<_> = grid.neighbours4(0, 1).collect();
+        top_edge.sort();
+        assert_eq!(top_edge, vec![(0, 0), (0, 2), (1, 1)]);
+
+        // positions outside the grid only yield neighbours that are inside
+        let outside: Vec<_> = grid.neighbours8(3, 3).collect();
+        assert_eq!(outside, vec![(2, 2)]);
+    }
+
+    #[test]
+    fn test_grid_rotate_counter_clockwise() {
+        let grid = Grid::parse("abc\ndef").unwrap();
+        assert_eq!(grid.rotate_counter_clockwise().to_string(), "cf\nbe\nad");
+    }
+
+    #[test]
+    fn test_grid_map() {
+        let grid = Grid::parse_with("12\n34", |c| c.to_digit(10).unwrap()).unwrap();
+        assert_eq!(grid.map(|n| n * 2).to_string(), "24\n68");
+    }
 }